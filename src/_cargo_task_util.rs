@@ -8,7 +8,18 @@
 //! are all defined in this module, and thus are available to task code too.
 //! But rust hoists all macros up to the root, so their docs are up there : )
 
-use std::{collections::BTreeMap, ffi::OsString, path::PathBuf, rc::Rc};
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+
+/// Tool versions found by [CTEnv::ensure_tool] in this process.
+/// Only kept in memory, other tasks (and later runs) probe again.
+static TOOL_CACHE: std::sync::Mutex<BTreeMap<String, String>> =
+    std::sync::Mutex::new(BTreeMap::new());
 
 /// Force install a new CTEnv
 #[doc(hidden)]
@@ -75,6 +86,46 @@ pub struct CTEnv {
     /// Additional arguments specified by user.
     pub arg_list: Vec<String>,
 
+    /// The file directives (`set_env` etc.) are appended to
+    /// for the parent task runner. (`CT_DIRECTIVE_FILE` env var)
+    pub directive_file: Option<PathBuf>,
+
+    /// Run cargo without accessing the network.
+    /// (`--offline` flag or `CT_OFFLINE` env var)
+    pub offline: bool,
+
+    /// Require the task workspace Cargo.lock to be up-to-date.
+    /// (`--locked` flag or `CT_LOCKED` env var)
+    pub locked: bool,
+
+    /// Equivalent to specifying both `offline` and `locked`.
+    /// (`--frozen` flag or `CT_FROZEN` env var)
+    pub frozen: bool,
+
+    /// Cargo build parallelism for task builds.
+    /// (`CT_JOBS` env var or `jobs` project config)
+    pub jobs: Option<String>,
+
+    /// Install missing `@ct-requires-tools@` tools instead of failing.
+    /// (`CT_INSTALL_TOOLS` env var or `install-tools` project config)
+    pub install_tools: bool,
+
+    /// Install `@ct-requires-tools@` tools with `cargo install --locked`
+    /// into the cargo-task managed root, see [CTTool::managed].
+    /// (`CT_INSTALL_TOOLS=managed` or `install-tools` project config)
+    pub managed_tools: bool,
+
+    /// Run the task list once per cargo workspace member.
+    /// (`--each-member` flag)
+    pub each_member: bool,
+
+    /// When running with `--each-member`, the current member crate name.
+    pub member_name: Option<String>,
+
+    /// When running with `--each-member`, the current member crate dir.
+    /// This is also the working directory of the task.
+    pub member_dir: Option<PathBuf>,
+
     /// All tasks defined in the task directory.
     pub tasks: BTreeMap<String, CTTaskMeta>,
 }
@@ -86,15 +137,193 @@ impl CTEnv {
     }
 
     /// Execute a rust std::process::Command
-    pub fn exec(&self, mut cmd: std::process::Command) -> std::io::Result<()> {
-        let non_zero_err = format!("{:?} exited non-zero", cmd);
-        if !cmd.spawn()?.wait()?.success() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                non_zero_err,
-            ));
+    /// Errors with a [CTExecError] if the command exits non-zero,
+    /// output goes to the console, so the error has no stderr tail.
+    pub fn exec(&self, cmd: std::process::Command) -> std::io::Result<()> {
+        CTCommand::from(cmd).run().map(|_| ())
+    }
+
+    /// Create a new [CTCommand] builder for running `program`
+    pub fn command<S: AsRef<OsStr>>(&self, program: S) -> CTCommand {
+        CTCommand::new(program)
+    }
+
+    /// Make sure `cargo <sub>` is available, installing it if needed.
+    /// See [CTTool] for more control over how the tool is installed.
+    pub fn ensure_cargo_tool<S: AsRef<str>>(
+        &self,
+        sub: S,
+    ) -> std::io::Result<()> {
+        self.ensure_tool(CTTool::new(sub))
+    }
+
+    /// Make sure a cargo tool is available, installing it if needed.
+    /// Tries the rustup component first (if any), then `cargo install`.
+    /// Successful probes are cached in memory for the rest of this task
+    /// process only. For a [CTTool::managed] tool, the managed root is
+    /// prepended to `PATH` with [CTEnv::prepend_path], so it is also
+    /// exported to the parent task runner, and later tasks find the tool.
+    pub fn ensure_tool(&self, tool: CTTool) -> std::io::Result<()> {
+        let version_req = match &tool.version_req {
+            Some(Ok(req)) => Some(req),
+            Some(Err(e)) => return Err(std::io::Error::other(e.clone())),
+            None => None,
+        };
+
+        let cached = TOOL_CACHE.lock().unwrap().get(&tool.sub).cloned();
+        if let Some(version) = cached {
+            match version_req {
+                Some(req) if !tool_version_matches(&version, req) => (),
+                _ => return Ok(()),
+            }
         }
-        Ok(())
+
+        if tool.managed {
+            self.export_tool_root();
+        }
+
+        let probe =
+            |tool: &CTTool| self.probe_tool(&tool.sub, None, version_req, None);
+
+        let mut status = probe(&tool);
+
+        if !matches!(status, ToolStatus::Ok(_)) {
+            self.install_tool(&tool, None)?;
+            if tool.managed {
+                self.export_tool_root();
+            }
+            status = probe(&tool);
+        }
+
+        match status {
+            ToolStatus::Ok(version) => {
+                TOOL_CACHE.lock().unwrap().insert(tool.sub, version);
+                Ok(())
+            }
+            ToolStatus::Missing => Err(std::io::Error::other(format!(
+                "cargo tool '{}' is not installed and could not be installed",
+                tool.sub,
+            ))),
+            ToolStatus::TooOld(version) => Err(std::io::Error::other(format!(
+                "cargo tool '{}' {} does not match required version {}{}",
+                tool.sub,
+                version,
+                version_req.map(|r| r.to_string()).unwrap_or_default(),
+                match &tool.component {
+                    Some(_) => ", try `rustup update`",
+                    None => "",
+                },
+            ))),
+        }
+    }
+
+    /// The root managed tools are installed into,
+    /// `<cargo-task target>/tools`.
+    pub(crate) fn tool_root(&self) -> PathBuf {
+        self.cargo_task_target.join("tools")
+    }
+
+    /// put the managed tool install root on PATH, if it exists
+    /// this is exported to the parent task runner, see `prepend_path`
+    fn export_tool_root(&self) {
+        let bin = self.tool_root().join("bin");
+        let on_path = std::env::var_os("PATH")
+            .map(|p| std::env::split_paths(&p).any(|p| p == bin))
+            .unwrap_or(false);
+        if bin.is_dir() && !on_path {
+            self.prepend_path(bin);
+        }
+    }
+
+    /// Check if a tool is installed, and matches `version_req`.
+    /// Tries `cargo <sub> --version`, then `<bin> --version` if given.
+    /// `vars` is the environment to probe with, or the current one if `None`.
+    pub(crate) fn probe_tool(
+        &self,
+        sub: &str,
+        bin: Option<&str>,
+        version_req: Option<&CTVersionReq>,
+        vars: Option<&BTreeMap<OsString, OsString>>,
+    ) -> ToolStatus {
+        let mut cmds = vec![self.cargo()];
+        cmds[0].arg(sub);
+        if let Some(bin) = bin {
+            cmds.push(std::process::Command::new(bin));
+        }
+
+        for mut cmd in cmds {
+            cmd.arg("--version");
+            if let Some(vars) = vars {
+                cmd.env_clear();
+                cmd.envs(vars);
+            }
+            let out = match CTCommand::from(cmd).capture().quiet().run() {
+                Ok(out) => out,
+                Err(_) => continue,
+            };
+
+            // e.g. "clippy 0.1.79 (...)" or "cargo-readme 3.3.1"
+            let version = out
+                .stdout
+                .split_whitespace()
+                .find(|w| w.starts_with(|c: char| c.is_ascii_digit()))
+                .unwrap_or_default()
+                .to_string();
+
+            return match version_req {
+                Some(req) if !tool_version_matches(&version, req) => {
+                    ToolStatus::TooOld(version)
+                }
+                _ => ToolStatus::Ok(version),
+            };
+        }
+
+        ToolStatus::Missing
+    }
+
+    /// Install a tool as its rustup component, falling back to
+    /// `cargo install` of its crate (into [CTEnv::tool_root] if managed).
+    /// `vars` is the environment to install with, or the current one if `None`.
+    pub(crate) fn install_tool(
+        &self,
+        tool: &CTTool,
+        vars: Option<&BTreeMap<OsString, OsString>>,
+    ) -> std::io::Result<()> {
+        let command = |program: &OsStr| {
+            let mut cmd = std::process::Command::new(program);
+            if let Some(vars) = vars {
+                cmd.env_clear();
+                cmd.envs(vars);
+            }
+            CTCommand::from(cmd)
+        };
+
+        if let Some(component) = &tool.component {
+            let res = command("rustup".as_ref())
+                .args(["component", "add", component])
+                .run();
+            if res.is_ok() || tool.krate.is_none() {
+                return res.map(|_| ());
+            }
+        }
+
+        let krate = match &tool.krate {
+            Some(krate) => krate,
+            None => {
+                return Err(std::io::Error::other(format!(
+                    "no rustup component or crate to install '{}' from",
+                    tool.sub,
+                )))
+            }
+        };
+        let mut cmd = command(self.cargo_path.as_os_str()).arg("install");
+        if tool.managed {
+            cmd = cmd.arg("--locked").arg("--root").arg(self.tool_root());
+        }
+        if let Some(Ok(req)) = &tool.version_req {
+            cmd = cmd.arg("--version").arg(req.to_string());
+        }
+        cmd.arg(krate).run().map(|_| ())
     }
 
     /// Export an environment variable up to the parent task runner env.
@@ -105,22 +334,169 @@ impl CTEnv {
 
         std::env::set_var(name, val);
 
-        let directive = format!("@ct-set-env@ {}={} @@\n", name, val);
+        self.write_directive(&format!("@ct-set-env@ {}={} @@\n", name, val));
+    }
 
-        let mut p = self.cargo_task_target.clone();
-        let directive_file_name =
-            format!("task-directive-{}.atat", std::process::id());
-        p.push(directive_file_name);
+    /// Remove an environment variable from the parent task runner env.
+    /// Also removes the variable from the current environment.
+    pub fn unset_env<N: AsRef<str>>(&self, name: N) {
+        let name = name.as_ref();
 
-        FRESH_DIRECTIVE.call_once(|| {
-            let _ = std::fs::remove_file(&p);
-        });
+        std::env::remove_var(name);
+
+        self.write_directive(&format!("@ct-unset-env@ {} @@\n", name));
+    }
+
+    /// Prepend a directory to `PATH` in the parent task runner env,
+    /// using the platform path separator.
+    /// Also updates `PATH` in the current environment.
+    pub fn prepend_path<P: AsRef<Path>>(&self, dir: P) {
+        self.edit_path("PATH", dir.as_ref(), true);
+    }
+
+    /// Append a directory to `PATH` in the parent task runner env,
+    /// using the platform path separator.
+    /// Also updates `PATH` in the current environment.
+    pub fn append_path<P: AsRef<Path>>(&self, dir: P) {
+        self.edit_path("PATH", dir.as_ref(), false);
+    }
+
+    fn edit_path(&self, name: &str, dir: &Path, prepend: bool) {
+        let mut paths = std::env::var_os(name)
+            .map(|p| std::env::split_paths(&p).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if prepend {
+            paths.insert(0, dir.to_owned());
+        } else {
+            paths.push(dir.to_owned());
+        }
+        std::env::set_var(name, ct_check_fatal!(std::env::join_paths(paths)));
+
+        let kind = if prepend {
+            "ct-path-prepend"
+        } else {
+            "ct-path-append"
+        };
+        self.write_directive(&format!(
+            "@{}@ {}={} @@\n",
+            kind,
+            name,
+            dir.to_string_lossy()
+        ));
+    }
+
+    /// Run another task (and its task dependencies) from within this task,
+    /// by re-entering the task runner (`CT_RUNNER_EXE`, or `cargo task`
+    /// if not set) with the same cargo-task environment.
+    /// Tasks that are already built are not rebuilt. Environment changes
+    /// exported by the invoked tasks are applied to this task, and are
+    /// exported on to the parent task runner.
+    pub fn run_task<N: AsRef<str>>(
+        &self,
+        name: N,
+        args: &[&str],
+    ) -> std::io::Result<()> {
+        let name = name.as_ref();
+
+        let stack = std::env::var("CT_TASK_STACK").unwrap_or_default();
+        let stack = stack.split_whitespace().collect::<Vec<_>>();
+        if stack.contains(&name) {
+            ct_fatal!("task cycle: {} -> {}", stack.join(" -> "), name);
+        }
+
+        static RUNS: std::sync::atomic::AtomicUsize =
+            std::sync::atomic::AtomicUsize::new(0);
+        let mut return_file = self.cargo_task_target.clone();
+        return_file.push(format!(
+            "task-return-{}-{}.atat",
+            std::process::id(),
+            RUNS.fetch_add(1, std::sync::atomic::Ordering::SeqCst),
+        ));
+
+        let mut cmd = match std::env::var_os("CT_RUNNER_EXE") {
+            Some(exe) => std::process::Command::new(exe),
+            None => self.cargo(),
+        };
+        cmd.arg("task");
+        cmd.arg(name);
+        if !args.is_empty() {
+            cmd.arg("--");
+            cmd.args(args);
+        }
+        if let Some(dir) = std::env::var_os("CT_RUNNER_WORK_DIR") {
+            cmd.current_dir(dir);
+        }
+        cmd.env("CT_RETURN_FILE", &return_file);
+        let res = self.exec(cmd);
+
+        let directives =
+            std::fs::read_to_string(&return_file).unwrap_or_default();
+        let _ = std::fs::remove_file(&return_file);
+        res?;
+
+        for directive in directives.split(" @@\n") {
+            let directive = match directive.trim_start().strip_prefix('@') {
+                Some(directive) => directive,
+                None => continue,
+            };
+            let (kind, value) = match directive.find('@') {
+                Some(idx) => (&directive[..idx], directive[idx + 1..].trim()),
+                None => continue,
+            };
+            if kind == "ct-unset-env" {
+                self.unset_env(value);
+                continue;
+            }
+            let (n, v) = match value.find('=') {
+                Some(idx) => (&value[..idx], &value[idx + 1..]),
+                None => continue,
+            };
+            match kind {
+                "ct-set-env" => self.set_env(n, v),
+                "ct-path-prepend" => self.edit_path(n, Path::new(v), true),
+                "ct-path-append" => self.edit_path(n, Path::new(v), false),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set an output value, available to tasks that depend on this task
+    /// via `CTEnv::output`. Values may span multiple lines.
+    pub fn set_output<K: AsRef<str>, V: AsRef<str>>(&self, key: K, val: V) {
+        self.write_directive(&format!(
+            "@ct-set-output@ {}={} @@\n",
+            key.as_ref(),
+            escape_output(val.as_ref()),
+        ));
+    }
+
+    /// Get an output value set by a task this task depends on.
+    pub fn output<T: AsRef<str>, K: AsRef<str>>(
+        &self,
+        task: T,
+        key: K,
+    ) -> Option<String> {
+        std::env::var(output_env_name(task.as_ref(), key.as_ref())).ok()
+    }
+
+    /// append a directive to the directive file of the parent task runner
+    /// each directive is written with a single append, so any processes
+    /// spawned by the task can safely write directives concurrently
+    fn write_directive(&self, directive: &str) {
+        let p = match &self.directive_file {
+            Some(p) => p,
+            None => {
+                ct_warn!("CT_DIRECTIVE_FILE not set, not exporting directive");
+                return;
+            }
+        };
 
         let mut f = ct_check_fatal!(std::fs::OpenOptions::new()
-            .write(true)
             .append(true)
             .create(true)
-            .open(&p));
+            .open(p));
 
         use std::io::Write;
         ct_check_fatal!(f.write_all(directive.as_bytes()));
@@ -128,7 +504,975 @@ impl CTEnv {
     }
 }
 
-static FRESH_DIRECTIVE: std::sync::Once = std::sync::Once::new();
+/// A cargo tool (`cargo <sub>`) to make sure is installed,
+/// with [CTEnv::ensure_tool].
+///
+/// ```ignore
+/// ct_env().ensure_tool(CTTool::new("readme").min_version("3").managed())?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct CTTool {
+    sub: String,
+    component: Option<String>,
+    krate: Option<String>,
+    version_req: Option<Result<CTVersionReq, String>>,
+    managed: bool,
+}
+
+impl CTTool {
+    /// A cargo tool run as `cargo <sub>`.
+    /// `clippy` and `fmt` are installed as rustup components,
+    /// anything else with `cargo install cargo-<sub>`.
+    pub fn new<S: AsRef<str>>(sub: S) -> Self {
+        let sub = sub.as_ref().to_string();
+        let (component, krate) = match sub.as_str() {
+            "clippy" => (Some("clippy".to_string()), None),
+            "fmt" => (Some("rustfmt".to_string()), None),
+            _ => (None, Some(format!("cargo-{}", sub))),
+        };
+        Self {
+            sub,
+            component,
+            krate,
+            version_req: None,
+            managed: false,
+        }
+    }
+
+    /// The rustup component providing this tool.
+    pub fn component<S: AsRef<str>>(mut self, component: S) -> Self {
+        self.component = Some(component.as_ref().to_string());
+        self
+    }
+
+    /// The crate to `cargo install` for this tool.
+    pub fn krate<S: AsRef<str>>(mut self, krate: S) -> Self {
+        self.krate = Some(krate.as_ref().to_string());
+        self
+    }
+
+    /// Require a version of the tool, as reported by `cargo <sub> --version`.
+    /// A plain version, e.g. `3`, means at least that version, otherwise
+    /// this is a [CTVersionReq], e.g. `>=3, <4`. An invalid requirement
+    /// is reported as an error by [CTEnv::ensure_tool].
+    pub fn min_version<S: AsRef<str>>(mut self, min_version: S) -> Self {
+        self.version_req = Some(parse_min_version(min_version.as_ref()));
+        self
+    }
+
+    /// Install with `cargo install --locked` into a cargo-task managed
+    /// root (`<cargo-task target>/tools`), instead of the user's cargo home.
+    /// [CTEnv::ensure_tool] exports the root's `bin` dir on `PATH`
+    /// to the parent task runner.
+    pub fn managed(mut self) -> Self {
+        self.managed = true;
+        self
+    }
+}
+
+/// The result of probing for a tool.
+#[derive(Debug)]
+pub(crate) enum ToolStatus {
+    /// found, with this version
+    Ok(String),
+
+    /// found, but not a required version (usually too old)
+    TooOld(String),
+
+    /// not a cargo subcommand, or on PATH
+    Missing,
+}
+
+/// parse a possibly partial version, e.g. `1.70` as `1.70.0`
+pub(crate) fn parse_padded_version(s: &str) -> Result<CTVersion, String> {
+    let mut s = s.trim().to_string();
+    for _ in s.split('.').count()..3 {
+        s.push_str(".0");
+    }
+    CTVersion::parse(&s)
+}
+
+/// parse a tool version requirement, a plain version means at least it
+pub(crate) fn parse_min_version(s: &str) -> Result<CTVersionReq, String> {
+    let s = s.trim();
+    if s.starts_with(|c: char| c.is_ascii_digit()) {
+        CTVersionReq::parse(&format!(">={}", s))
+    } else {
+        CTVersionReq::parse(s)
+    }
+}
+
+/// does a tool version (e.g. `1.7.0-stable`) match `req`
+/// any pre-release / build suffix of the tool version is ignored
+pub(crate) fn tool_version_matches(version: &str, req: &CTVersionReq) -> bool {
+    let version = version.split(['-', '+']).next().unwrap_or_default();
+    match parse_padded_version(version) {
+        Ok(version) => req.matches(&version),
+        Err(_) => false,
+    }
+}
+
+/// is a tool version (e.g. `1.7.0-stable`) at least `min`
+/// any pre-release / build suffix of the tool version is ignored
+pub(crate) fn version_at_least(version: &str, min: &str) -> bool {
+    let version = version.split(['-', '+']).next().unwrap_or_default();
+    match (parse_padded_version(version), parse_padded_version(min)) {
+        (Ok(version), Ok(min)) => version >= min,
+        _ => false,
+    }
+}
+
+/// Command execution builder with output capture.
+///
+/// ```ignore
+/// let out = ct_env()
+///     .command("git")
+///     .args(&["rev-parse", "HEAD"])
+///     .capture()
+///     .run()?;
+/// let rev = out.stdout.trim();
+/// ```
+///
+/// The command line is echoed at info level before running.
+/// By default, output goes straight to the console, and a non-zero exit
+/// is an error. Errors from a failed or timed out command wrap a
+/// [CTExecError], retrievable with `err.get_ref()` and `downcast_ref`.
+#[derive(Debug)]
+pub struct CTCommand {
+    cmd: std::process::Command,
+    capture: bool,
+    log_file: Option<PathBuf>,
+    timeout: Option<Duration>,
+    check: bool,
+    echo: bool,
+}
+
+impl From<std::process::Command> for CTCommand {
+    fn from(cmd: std::process::Command) -> Self {
+        Self {
+            cmd,
+            capture: false,
+            log_file: None,
+            timeout: None,
+            check: true,
+            echo: true,
+        }
+    }
+}
+
+impl CTCommand {
+    /// Create a new command builder for running `program`
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
+        std::process::Command::new(program).into()
+    }
+
+    /// Add an argument
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.cmd.arg(arg);
+        self
+    }
+
+    /// Add multiple arguments
+    pub fn args<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        mut self,
+        args: I,
+    ) -> Self {
+        self.cmd.args(args);
+        self
+    }
+
+    /// Set an environment variable for this command only
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(mut self, k: K, v: V) -> Self {
+        self.cmd.env(k, v);
+        self
+    }
+
+    /// Remove an environment variable for this command only
+    pub fn env_remove<K: AsRef<OsStr>>(mut self, k: K) -> Self {
+        self.cmd.env_remove(k);
+        self
+    }
+
+    /// Set the working directory for this command
+    pub fn current_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.cmd.current_dir(dir);
+        self
+    }
+
+    /// Capture stdout / stderr into the [CTOutput]
+    /// instead of printing them to the console.
+    pub fn capture(mut self) -> Self {
+        self.capture = true;
+        self
+    }
+
+    /// Also append stdout / stderr to a log file. Output is still printed
+    /// to the console (unless `capture()` is set), and is also returned in
+    /// the [CTOutput].
+    pub fn tee<P: AsRef<Path>>(mut self, log_file: P) -> Self {
+        self.log_file = Some(log_file.as_ref().to_owned());
+        self
+    }
+
+    /// Kill the command if it runs longer than `timeout`.
+    /// With `capture()` or `tee()`, any stderr read before the kill is kept
+    /// in the error's stderr tail; processes the command spawned itself are
+    /// not waited for.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Whether a non-zero exit is an error (default `true`)
+    pub fn check(mut self, check: bool) -> Self {
+        self.check = check;
+        self
+    }
+
+    /// Don't echo the command line before running
+    pub fn quiet(mut self) -> Self {
+        self.echo = false;
+        self
+    }
+
+    /// The command line, quoted for display
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.cmd.get_program())
+            .chain(self.cmd.get_args())
+            .map(|a| quote_arg(&a.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Run the command to completion.
+    /// Output is only read (for the [CTOutput] and the stderr tail of a
+    /// [CTExecError]) with `capture()` or `tee()`, otherwise the command
+    /// writes straight to the console, so tools still detect a terminal.
+    pub fn run(mut self) -> std::io::Result<CTOutput> {
+        use std::process::Stdio;
+
+        let command_line = self.command_line();
+        if self.echo {
+            ct_info!("exec: {}", command_line);
+        }
+
+        // only pipe output if we need it, so tools still see a terminal
+        let piped = self.capture || self.log_file.is_some();
+        if piped {
+            self.cmd.stdout(Stdio::piped());
+            self.cmd.stderr(Stdio::piped());
+        }
+
+        let log_file = match &self.log_file {
+            Some(p) => Some(std::sync::Arc::new(std::sync::Mutex::new(
+                std::fs::OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(p)?,
+            ))),
+            None => None,
+        };
+
+        let mut child = self.cmd.spawn()?;
+
+        // the readers fill shared buffers, so we can return what was read
+        // so far on timeout, without waiting for grandchildren that may
+        // still hold the pipes open
+        let stdout_buf = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let stderr_buf = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let stdout = child.stdout.take().map(|s| {
+            let log_file = log_file.clone();
+            let out = stdout_buf.clone();
+            let console = !self.capture;
+            std::thread::spawn(move || {
+                tee_stream(s, console.then(std::io::stdout), log_file, out)
+            })
+        });
+        let stderr = child.stderr.take().map(|s| {
+            let log_file = log_file.clone();
+            let out = stderr_buf.clone();
+            let console = !self.capture;
+            std::thread::spawn(move || {
+                tee_stream(s, console.then(std::io::stderr), log_file, out)
+            })
+        });
+
+        let status = match self.timeout {
+            None => Some(child.wait()?),
+            Some(timeout) => {
+                let start = std::time::Instant::now();
+                loop {
+                    if let Some(status) = child.try_wait()? {
+                        break Some(status);
+                    }
+                    if start.elapsed() >= timeout {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        break None;
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+            }
+        };
+
+        // only wait for the readers if the child exited on its own
+        if status.is_some() {
+            let join = |h: Option<std::thread::JoinHandle<_>>| match h {
+                Some(h) => h.join().unwrap_or(Ok(())),
+                None => Ok(()),
+            };
+            join(stdout)?;
+            join(stderr)?;
+        }
+        let take = |buf: &std::sync::Mutex<Vec<u8>>| {
+            let buf = buf.lock().unwrap_or_else(|e| e.into_inner());
+            String::from_utf8_lossy(&buf).to_string()
+        };
+        let stdout = take(&stdout_buf);
+        let stderr = take(&stderr_buf);
+
+        let status = match status {
+            Some(status) => status,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    CTExecError {
+                        command_line,
+                        status: None,
+                        stderr_tail: stderr_tail(&stderr),
+                    },
+                ))
+            }
+        };
+
+        if self.check && !status.success() {
+            return Err(std::io::Error::other(CTExecError {
+                command_line,
+                status: Some(status),
+                stderr_tail: stderr_tail(&stderr),
+            }));
+        }
+
+        Ok(CTOutput {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+/// The result of running a [CTCommand].
+/// `stdout` and `stderr` are only filled in if the command was run
+/// with `capture()` or `tee()`.
+#[derive(Debug)]
+pub struct CTOutput {
+    /// exit status of the command
+    pub status: std::process::ExitStatus,
+
+    /// captured stdout
+    pub stdout: String,
+
+    /// captured stderr
+    pub stderr: String,
+}
+
+/// A [CTCommand] that exited non-zero or timed out.
+#[derive(Debug)]
+pub struct CTExecError {
+    /// the quoted command line
+    pub command_line: String,
+
+    /// exit status of the command, `None` if it timed out
+    pub status: Option<std::process::ExitStatus>,
+
+    /// the last lines of stderr, empty unless the command was run
+    /// with `capture()` or `tee()`
+    pub stderr_tail: String,
+}
+
+impl std::fmt::Display for CTExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.status {
+            Some(status) => {
+                write!(f, "`{}` failed: {}", self.command_line, status)?
+            }
+            None => write!(f, "`{}` timed out", self.command_line)?,
+        }
+        if !self.stderr_tail.is_empty() {
+            write!(f, "\n--- stderr ---\n{}", self.stderr_tail)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CTExecError {}
+
+/// number of stderr lines included in a CTExecError
+const STDERR_TAIL_LINES: usize = 20;
+
+/// the last STDERR_TAIL_LINES lines of captured stderr
+fn stderr_tail(stderr: &str) -> String {
+    let lines = stderr.lines().collect::<Vec<_>>();
+    let start = lines.len().saturating_sub(STDERR_TAIL_LINES);
+    lines[start..].join("\n")
+}
+
+/// copy a child output stream to the console and / or a log file,
+/// appending everything read to `out`
+fn tee_stream<R: std::io::Read, W: std::io::Write>(
+    mut stream: R,
+    mut console: Option<W>,
+    log_file: Option<std::sync::Arc<std::sync::Mutex<std::fs::File>>>,
+    out: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut buf = [0; 4096];
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        if let Some(console) = &mut console {
+            console.write_all(&buf[..n])?;
+            console.flush()?;
+        }
+        if let Some(log_file) = &log_file {
+            log_file.lock().unwrap().write_all(&buf[..n])?;
+        }
+        out.lock().unwrap().extend_from_slice(&buf[..n]);
+    }
+}
+
+/// quote a command line argument for display, if needed
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c))
+    {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+/// A SemVer 2.0 version, e.g. `0.1.0-beta.1+build.5`.
+/// Ordering follows the SemVer spec: pre-release versions are lower than
+/// the release, and build metadata is ignored.
+///
+/// ```ignore
+/// let v: CTVersion = "0.1.0-beta.1".parse()?;
+/// assert!(v < "0.1.0".parse()?);
+/// ```
+#[derive(Debug, Clone, Eq)]
+pub struct CTVersion {
+    /// major version
+    pub major: u64,
+
+    /// minor version
+    pub minor: u64,
+
+    /// patch version
+    pub patch: u64,
+
+    /// pre-release identifiers (empty for a release)
+    pub pre: Vec<CTPreId>,
+
+    /// build metadata identifiers
+    pub build: Vec<String>,
+}
+
+/// A SemVer pre-release identifier.
+/// Numeric identifiers sort lower than alphanumeric ones.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CTPreId {
+    /// a numeric identifier, compared numerically
+    Numeric(u64),
+
+    /// an alphanumeric identifier, compared lexically
+    Alpha(String),
+}
+
+impl CTVersion {
+    /// Parse a full `major.minor.patch[-pre][+build]` version.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let err = |e: String| format!("invalid version '{}': {}", s, e);
+
+        let (rest, build) = match s.find('+') {
+            Some(idx) => (&s[..idx], parse_build(&s[idx + 1..]).map_err(err)?),
+            None => (s, Vec::new()),
+        };
+        let (core, pre) = match rest.find('-') {
+            Some(idx) => {
+                (&rest[..idx], parse_pre(&rest[idx + 1..]).map_err(err)?)
+            }
+            None => (rest, Vec::new()),
+        };
+
+        let parts = core.split('.').collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(err("expected major.minor.patch".to_string()));
+        }
+
+        Ok(Self {
+            major: parse_version_num(parts[0]).map_err(err)?,
+            minor: parse_version_num(parts[1]).map_err(err)?,
+            patch: parse_version_num(parts[2]).map_err(err)?,
+            pre,
+            build,
+        })
+    }
+}
+
+impl std::str::FromStr for CTVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for CTVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            let pre = self
+                .pre
+                .iter()
+                .map(|p| match p {
+                    CTPreId::Numeric(n) => n.to_string(),
+                    CTPreId::Alpha(a) => a.clone(),
+                })
+                .collect::<Vec<_>>();
+            write!(f, "-{}", pre.join("."))?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for CTVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl PartialOrd for CTVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CTVersion {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.major
+            .cmp(&other.major)
+            .then(self.minor.cmp(&other.minor))
+            .then(self.patch.cmp(&other.patch))
+            .then_with(|| cmp_pre(&self.pre, &other.pre))
+    }
+}
+
+/// A cargo-style version requirement, e.g. `>=0.0.13, <0.1`.
+/// Supports `=`, `>`, `>=`, `<`, `<=`, `~`, `^` (the default)
+/// and `*` wildcards. As with cargo, pre-release versions only match
+/// if a comparator names the same `major.minor.patch` with a pre-release.
+#[derive(Debug, Clone)]
+pub struct CTVersionReq {
+    src: String,
+    comparators: Vec<Comparator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VersionOp {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+}
+
+#[derive(Debug, Clone)]
+struct Comparator {
+    op: VersionOp,
+    major: u64,
+    minor: Option<u64>,
+    patch: Option<u64>,
+    pre: Vec<CTPreId>,
+}
+
+impl CTVersionReq {
+    /// Parse a comma-separated list of version comparators.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let src = s.trim().to_string();
+        let err = |e: String| format!("invalid version req '{}': {}", src, e);
+
+        if src.is_empty() {
+            return Err(err("empty".to_string()));
+        }
+
+        let mut comparators = Vec::new();
+        for c in src.split(',') {
+            if let Some(c) = parse_comparator(c.trim()).map_err(err)? {
+                comparators.push(c);
+            }
+        }
+
+        Ok(Self { src, comparators })
+    }
+
+    /// Does `version` satisfy this requirement?
+    pub fn matches(&self, version: &CTVersion) -> bool {
+        self.comparators.iter().all(|c| c.matches(version))
+            && (version.pre.is_empty()
+                || self.comparators.iter().any(|c| {
+                    c.major == version.major
+                        && c.minor == Some(version.minor)
+                        && c.patch == Some(version.patch)
+                        && !c.pre.is_empty()
+                }))
+    }
+}
+
+impl std::str::FromStr for CTVersionReq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for CTVersionReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.src)
+    }
+}
+
+impl Comparator {
+    fn matches(&self, v: &CTVersion) -> bool {
+        match self.op {
+            VersionOp::Exact => self.matches_exact(v),
+            VersionOp::Greater => self.matches_greater(v),
+            VersionOp::GreaterEq => {
+                self.matches_exact(v) || self.matches_greater(v)
+            }
+            VersionOp::Less => self.matches_less(v),
+            VersionOp::LessEq => self.matches_exact(v) || self.matches_less(v),
+            VersionOp::Tilde => self.matches_tilde(v),
+            VersionOp::Caret => self.matches_caret(v),
+        }
+    }
+
+    fn matches_exact(&self, v: &CTVersion) -> bool {
+        v.major == self.major
+            && (self.minor.is_none() || self.minor == Some(v.minor))
+            && (self.patch.is_none() || self.patch == Some(v.patch))
+            && v.pre == self.pre
+    }
+
+    fn matches_greater(&self, v: &CTVersion) -> bool {
+        if v.major != self.major {
+            return v.major > self.major;
+        }
+        match self.minor {
+            None => return false,
+            Some(m) if v.minor != m => return v.minor > m,
+            _ => (),
+        }
+        match self.patch {
+            None => return false,
+            Some(p) if v.patch != p => return v.patch > p,
+            _ => (),
+        }
+        cmp_pre(&v.pre, &self.pre) == std::cmp::Ordering::Greater
+    }
+
+    fn matches_less(&self, v: &CTVersion) -> bool {
+        if v.major != self.major {
+            return v.major < self.major;
+        }
+        match self.minor {
+            None => return false,
+            Some(m) if v.minor != m => return v.minor < m,
+            _ => (),
+        }
+        match self.patch {
+            None => return false,
+            Some(p) if v.patch != p => return v.patch < p,
+            _ => (),
+        }
+        cmp_pre(&v.pre, &self.pre) == std::cmp::Ordering::Less
+    }
+
+    fn matches_tilde(&self, v: &CTVersion) -> bool {
+        if v.major != self.major {
+            return false;
+        }
+        if let Some(m) = self.minor {
+            if v.minor != m {
+                return false;
+            }
+        }
+        if let Some(p) = self.patch {
+            if v.patch != p {
+                return v.patch > p;
+            }
+        }
+        cmp_pre(&v.pre, &self.pre) != std::cmp::Ordering::Less
+    }
+
+    fn matches_caret(&self, v: &CTVersion) -> bool {
+        if v.major != self.major {
+            return false;
+        }
+        let minor = match self.minor {
+            None => return true,
+            Some(minor) => minor,
+        };
+        let patch = match self.patch {
+            None if self.major > 0 => return v.minor >= minor,
+            None => return v.minor == minor,
+            Some(patch) => patch,
+        };
+        if self.major > 0 {
+            if v.minor != minor {
+                return v.minor > minor;
+            } else if v.patch != patch {
+                return v.patch > patch;
+            }
+        } else if minor > 0 {
+            if v.minor != minor {
+                return false;
+            } else if v.patch != patch {
+                return v.patch > patch;
+            }
+        } else if v.minor != minor || v.patch != patch {
+            return false;
+        }
+        cmp_pre(&v.pre, &self.pre) != std::cmp::Ordering::Less
+    }
+}
+
+/// parse a single comparator, `None` for a bare `*` wildcard
+fn parse_comparator(s: &str) -> Result<Option<Comparator>, String> {
+    let (op, rest) = if let Some(rest) = s.strip_prefix(">=") {
+        (Some(VersionOp::GreaterEq), rest)
+    } else if let Some(rest) = s.strip_prefix("<=") {
+        (Some(VersionOp::LessEq), rest)
+    } else if let Some(rest) = s.strip_prefix('>') {
+        (Some(VersionOp::Greater), rest)
+    } else if let Some(rest) = s.strip_prefix('<') {
+        (Some(VersionOp::Less), rest)
+    } else if let Some(rest) = s.strip_prefix('=') {
+        (Some(VersionOp::Exact), rest)
+    } else if let Some(rest) = s.strip_prefix('~') {
+        (Some(VersionOp::Tilde), rest)
+    } else if let Some(rest) = s.strip_prefix('^') {
+        (Some(VersionOp::Caret), rest)
+    } else {
+        (None, s)
+    };
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return Err(format!("missing version in '{}'", s));
+    }
+
+    // build metadata is ignored in requirements
+    let rest = rest.split('+').next().unwrap_or_default();
+    let (core, pre) = match rest.find('-') {
+        Some(idx) => (&rest[..idx], parse_pre(&rest[idx + 1..])?),
+        None => (rest, Vec::new()),
+    };
+
+    if core.split('.').count() > 3 {
+        return Err(format!("too many version parts in '{}'", s));
+    }
+
+    let mut nums = Vec::new();
+    let mut wildcard = false;
+    for part in core.split('.') {
+        if part == "*" || part == "x" || part == "X" {
+            wildcard = true;
+            continue;
+        }
+        if wildcard {
+            return Err(format!(
+                "unexpected '{}' after wildcard in '{}'",
+                part, s
+            ));
+        }
+        nums.push(parse_version_num(part)?);
+    }
+    if wildcard && !matches!(op, None | Some(VersionOp::Exact)) {
+        return Err(format!(
+            "wildcards can't be combined with operators: '{}'",
+            s
+        ));
+    }
+    if !pre.is_empty() && nums.len() != 3 {
+        return Err(format!("pre-release requires major.minor.patch: '{}'", s));
+    }
+    if nums.is_empty() {
+        return Ok(None);
+    }
+
+    let op = match op {
+        Some(op) => op,
+        None if wildcard => VersionOp::Exact,
+        None => VersionOp::Caret,
+    };
+    Ok(Some(Comparator {
+        op,
+        major: nums[0],
+        minor: nums.get(1).copied(),
+        patch: nums.get(2).copied(),
+        pre,
+    }))
+}
+
+/// parse a numeric version part (no leading zeros)
+fn parse_version_num(s: &str) -> Result<u64, String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("'{}' is not a number", s));
+    }
+    if s.len() > 1 && s.starts_with('0') {
+        return Err(format!("'{}' has a leading zero", s));
+    }
+    s.parse().map_err(|e| format!("'{}': {}", s, e))
+}
+
+/// parse dot-separated pre-release identifiers
+fn parse_pre(s: &str) -> Result<Vec<CTPreId>, String> {
+    let mut out = Vec::new();
+    for id in s.split('.') {
+        check_ident(id)?;
+        if id.chars().all(|c| c.is_ascii_digit()) {
+            out.push(CTPreId::Numeric(parse_version_num(id)?));
+        } else {
+            out.push(CTPreId::Alpha(id.to_string()));
+        }
+    }
+    Ok(out)
+}
+
+/// parse dot-separated build metadata identifiers
+fn parse_build(s: &str) -> Result<Vec<String>, String> {
+    s.split('.')
+        .map(|id| check_ident(id).map(|_| id.to_string()))
+        .collect()
+}
+
+/// identifiers are non-empty `[0-9A-Za-z-]`
+fn check_ident(id: &str) -> Result<(), String> {
+    if id.is_empty() {
+        return Err("empty identifier".to_string());
+    }
+    if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("invalid identifier '{}'", id));
+    }
+    Ok(())
+}
+
+/// compare pre-release identifiers, a release sorts above any pre-release
+fn cmp_pre(a: &[CTPreId], b: &[CTPreId]) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.cmp(b),
+    }
+}
+
+/// The directive file format version written by the task runner.
+pub const DIRECTIVE_VERSION: &str = "1";
+
+/// The environment variable a task output is passed to dependent tasks in,
+/// `CT_OUTPUT_<task>__<key>`, see [env_name_encode]. As encoded names
+/// never contain `__`, it only ever appears as the separator.
+pub fn output_env_name(task: &str, key: &str) -> String {
+    format!(
+        "CT_OUTPUT_{}__{}",
+        env_name_encode(task),
+        env_name_encode(key),
+    )
+}
+
+/// Encode a task name (or output key) for use in an environment variable
+/// name. Any character other than an ascii letter or digit is written as
+/// `_` and two hex digits per utf8 byte, e.g. `nest/my-task` becomes
+/// `nest_2fmy_2dtask`.
+pub(crate) fn env_name_encode(name: &str) -> String {
+    let mut out = String::new();
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("_{:02x}", b));
+        }
+    }
+    out
+}
+
+/// Reverse [env_name_encode], `None` if `encoded` is not a valid encoding.
+pub(crate) fn env_name_decode(encoded: &str) -> Option<String> {
+    let mut out = Vec::new();
+    let mut bytes = encoded.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'_' {
+            out.push(b);
+            continue;
+        }
+        let hex = [bytes.next()?, bytes.next()?];
+        let hex = std::str::from_utf8(&hex).ok()?;
+        out.push(u8::from_str_radix(hex, 16).ok()?);
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Escape an output value so it survives AtAt encoding intact.
+pub fn escape_output(val: &str) -> String {
+    let mut out = String::new();
+    for c in val.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ' ' => out.push_str("\\s"),
+            '@' => out.push_str("\\a"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverse `escape_output`.
+pub fn unescape_output(val: &str) -> String {
+    let mut out = String::new();
+    let mut chars = val.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('s') => out.push(' '),
+            Some('a') => out.push('@'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
 
 /// Cargo-task task metadata struct.
 #[derive(Debug)]
@@ -143,9 +1487,30 @@ pub struct CTTaskMeta {
     /// Minimum cargo-task utility version required for this task.
     pub min_version: Option<String>,
 
+    /// cargo-task version requirement for this task, e.g. `>=0.0.13, <0.1`
+    pub version_req: Option<String>,
+
+    /// minimum rust (rustc) version required to build this task
+    pub rust_version: Option<String>,
+
+    /// rustup toolchain to build this task with, e.g. `nightly`
+    pub toolchain: Option<String>,
+
     /// task "crate" path
     pub path: PathBuf,
 
+    /// the directory containing the .cargo-task directory defining this task.
+    /// The task is executed in this directory.
+    pub work_dir: PathBuf,
+
+    /// the task namespace (the directory name of `work_dir`, qualified
+    /// with parent directory names if an inner level uses the same name).
+    /// Outer tasks overridden by an inner task are named `namespace:name`.
+    pub namespace: String,
+
+    /// the build target directory for the tasks of `work_dir`.
+    pub target_dir: PathBuf,
+
     /// does this path run on default `cargo task` execution?
     pub default: bool,
 
@@ -156,13 +1521,54 @@ pub struct CTTaskMeta {
     /// help info for this task
     pub help: String,
 
+    /// rust edition for a script task
+    /// (crate tasks specify this in their Cargo.toml)
+    pub edition: String,
+
+    /// cargo profile this task will be built with
+    pub profile: String,
+
     /// any cargo (Cargo.toml) dependencies for a script task
     pub cargo_deps: Option<String>,
 
+    /// raw Cargo.toml fragment to merge into a script task's manifest
+    pub cargo_toml: Option<String>,
+
+    /// build.rs source for a script task
+    pub build_rs: Option<String>,
+
+    /// crates from the project's cargo workspace this task depends on
+    pub project_crates: Vec<String>,
+
+    /// cargo features to enable when building this task
+    pub features: Vec<String>,
+
+    /// dotenv-style files (relative to `work_dir`) to load into
+    /// the task's environment
+    pub env_files: Vec<String>,
+
+    /// which later tasks see the variables this task exports via `set_env`:
+    /// "global" (all later tasks) or "dependents" (tasks depending on it)
+    pub env_scope: String,
+
+    /// tools (`name` or `name>=version`) that must be on PATH
+    /// or available as cargo subcommands before this task runs
+    pub requires_tools: Vec<String>,
+
     /// any cargo-task task dependencies
     pub task_deps: Vec<String>,
 }
 
+impl CTTaskMeta {
+    /// the task name without any `namespace:` qualifier
+    pub fn base_name(&self) -> &str {
+        match self.name.rfind(':') {
+            Some(idx) => &self.name[idx + 1..],
+            None => &self.name,
+        }
+    }
+}
+
 /// Log Level enum for CT logging
 #[derive(Clone, Copy)]
 pub enum CTLogLevel {
@@ -183,7 +1589,7 @@ pub fn ct_log(lvl: CTLogLevel, text: &str) {
 
     let task_name = std::env::var_os("CT_CUR_TASK")
         .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "".to_string());
+        .unwrap_or_default();
 
     let t_colon = if task_name.is_empty() { "" } else { ":" };
 
@@ -256,6 +1662,8 @@ fn priv_new_env() -> Rc<CTEnv> {
             Some(cargo_task_target) => cargo_task_target,
             None => ct_fatal!("CT_TARGET environment variable not set"),
         };
+    let directive_file =
+        std::env::var_os("CT_DIRECTIVE_FILE").map(PathBuf::from);
     let task_list = match std::env::var_os("CT_TASKS") {
         Some(args) => args
             .to_string_lossy()
@@ -272,6 +1680,16 @@ fn priv_new_env() -> Rc<CTEnv> {
             .collect::<Vec<_>>(),
         None => Vec::with_capacity(0),
     };
+    let offline = std::env::var_os("CT_OFFLINE").is_some();
+    let locked = std::env::var_os("CT_LOCKED").is_some();
+    let frozen = std::env::var_os("CT_FROZEN").is_some();
+    let jobs = std::env::var("CT_JOBS").ok();
+    let install_tools = std::env::var_os("CT_INSTALL_TOOLS").is_some();
+    let managed_tools =
+        std::env::var("CT_INSTALL_TOOLS").as_deref() == Ok("managed");
+    let each_member = std::env::var_os("CT_EACH_MEMBER").is_some();
+    let member_name = std::env::var("CT_MEMBER_NAME").ok();
+    let member_dir = std::env::var_os("CT_MEMBER_DIR").map(PathBuf::from);
     let tasks = ct_check_fatal!(enumerate_task_metadata());
 
     Rc::new(CTEnv {
@@ -281,6 +1699,16 @@ fn priv_new_env() -> Rc<CTEnv> {
         cargo_task_target,
         task_list,
         arg_list,
+        directive_file,
+        offline,
+        locked,
+        frozen,
+        jobs,
+        install_tools,
+        managed_tools,
+        each_member,
+        member_name,
+        member_dir,
         tasks,
     })
 }
@@ -295,33 +1723,115 @@ fn enumerate_task_metadata(
     for (env_k, env_v) in env.iter() {
         let env_k = env_k.to_string_lossy();
         if env_k.starts_with("CT_TASK_") && env_k.ends_with("_PATH") {
-            let name = env_k[8..env_k.len() - 5].to_string();
-            let script_name = format!("CT_TASK_{}_IS_SCRIPT", name);
+            let key = &env_k[8..env_k.len() - 5];
+            let name = match env_name_decode(key) {
+                Some(name) => name,
+                None => continue,
+            };
+            let script_name = format!("CT_TASK_{}_IS_SCRIPT", key);
             let is_script = env.contains_key(&OsString::from(script_name));
-            let mv_name = format!("CT_TASK_{}_MIN_VER", name);
+            let mv_name = format!("CT_TASK_{}_MIN_VER", key);
             let min_version = env
                 .get(&OsString::from(mv_name))
                 .map(|v| v.to_string_lossy().to_string());
-            let def_name = format!("CT_TASK_{}_DEFAULT", name);
+            let vr_name = format!("CT_TASK_{}_VERSION_REQ", key);
+            let version_req = env
+                .get(&OsString::from(vr_name))
+                .map(|v| v.to_string_lossy().to_string());
+            let rv_name = format!("CT_TASK_{}_RUST_VERSION", key);
+            let rust_version = env
+                .get(&OsString::from(rv_name))
+                .map(|v| v.to_string_lossy().to_string());
+            let tc_name = format!("CT_TASK_{}_TOOLCHAIN", key);
+            let toolchain = env
+                .get(&OsString::from(tc_name))
+                .map(|v| v.to_string_lossy().to_string());
+            let def_name = format!("CT_TASK_{}_DEFAULT", key);
             let default = env.contains_key(&OsString::from(def_name));
-            let bs_name = format!("CT_TASK_{}_BOOTSTRAP", name);
+            let bs_name = format!("CT_TASK_{}_BOOTSTRAP", key);
             let bootstrap = env.contains_key(&OsString::from(bs_name));
-            let help_name = format!("CT_TASK_{}_HELP", name);
+            let help_name = format!("CT_TASK_{}_HELP", key);
             let help = env
                 .get(&OsString::from(help_name))
                 .map(|s| s.to_string_lossy().to_string())
                 .unwrap_or_else(|| "".to_string());
-            let deps_name = format!("CT_TASK_{}_CARGO_DEPS", name);
+            let ed_name = format!("CT_TASK_{}_EDITION", key);
+            let edition = env
+                .get(&OsString::from(ed_name))
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let prof_name = format!("CT_TASK_{}_PROFILE", key);
+            let profile = env
+                .get(&OsString::from(prof_name))
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_else(|| "release".to_string());
+            let deps_name = format!("CT_TASK_{}_CARGO_DEPS", key);
             let cargo_deps = env
                 .get(&OsString::from(deps_name))
                 .map(|v| v.to_string_lossy().to_string());
-            let deps_name = format!("CT_TASK_{}_TASK_DEPS", name);
+            let toml_name = format!("CT_TASK_{}_CARGO_TOML", key);
+            let cargo_toml = env
+                .get(&OsString::from(toml_name))
+                .map(|v| v.to_string_lossy().to_string());
+            let brs_name = format!("CT_TASK_{}_BUILD_RS", key);
+            let build_rs = env
+                .get(&OsString::from(brs_name))
+                .map(|v| v.to_string_lossy().to_string());
+            let pc_name = format!("CT_TASK_{}_PROJECT_CRATES", key);
+            let mut project_crates = Vec::new();
+            if let Some(crates) = env.get(&OsString::from(pc_name)) {
+                for c in crates.to_string_lossy().split_whitespace() {
+                    project_crates.push(c.to_string());
+                }
+            }
+            let feat_name = format!("CT_TASK_{}_FEATURES", key);
+            let mut features = Vec::new();
+            if let Some(feats) = env.get(&OsString::from(feat_name)) {
+                for feat in feats.to_string_lossy().split_whitespace() {
+                    features.push(feat.to_string());
+                }
+            }
+            let ef_name = format!("CT_TASK_{}_ENV_FILES", key);
+            let mut env_files = Vec::new();
+            if let Some(files) = env.get(&OsString::from(ef_name)) {
+                for file in files.to_string_lossy().split_whitespace() {
+                    env_files.push(file.to_string());
+                }
+            }
+            let scope_name = format!("CT_TASK_{}_ENV_SCOPE", key);
+            let env_scope = env
+                .get(&OsString::from(scope_name))
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_else(|| "global".to_string());
+            let rt_name = format!("CT_TASK_{}_REQUIRES_TOOLS", key);
+            let mut requires_tools = Vec::new();
+            if let Some(tools) = env.get(&OsString::from(rt_name)) {
+                for tool in tools.to_string_lossy().split_whitespace() {
+                    requires_tools.push(tool.to_string());
+                }
+            }
+            let deps_name = format!("CT_TASK_{}_TASK_DEPS", key);
             let mut task_deps = Vec::new();
             if let Some(deps) = env.get(&OsString::from(deps_name)) {
                 for dep in deps.to_string_lossy().split_whitespace() {
                     task_deps.push(dep.to_string());
                 }
             }
+            let wd_name = format!("CT_TASK_{}_WORK_DIR", key);
+            let work_dir = env
+                .get(&OsString::from(wd_name))
+                .map(PathBuf::from)
+                .unwrap_or_default();
+            let ns_name = format!("CT_TASK_{}_NAMESPACE", key);
+            let namespace = env
+                .get(&OsString::from(ns_name))
+                .map(|v| v.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let target_name = format!("CT_TASK_{}_TARGET", key);
+            let target_dir = env
+                .get(&OsString::from(target_name))
+                .map(PathBuf::from)
+                .unwrap_or_default();
             let path = PathBuf::from(env_v);
             out.insert(
                 name.clone(),
@@ -329,11 +1839,26 @@ fn enumerate_task_metadata(
                     name,
                     is_script,
                     min_version,
+                    version_req,
+                    rust_version,
+                    toolchain,
                     path,
+                    work_dir,
+                    namespace,
+                    target_dir,
                     default,
                     bootstrap,
                     help,
+                    edition,
+                    profile,
                     cargo_deps,
+                    cargo_toml,
+                    build_rs,
+                    project_crates,
+                    features,
+                    env_files,
+                    env_scope,
+                    requires_tools,
                     task_deps,
                 },
             );
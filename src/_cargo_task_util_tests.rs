@@ -0,0 +1,98 @@
+//! Tests for the `_cargo_task_util` module, kept out of the module itself
+//! because its source is copied into the generated `cargo_task_util` crate.
+
+use crate::_cargo_task_util::*;
+
+fn v(s: &str) -> CTVersion {
+    CTVersion::parse(s).unwrap()
+}
+
+fn req(s: &str) -> CTVersionReq {
+    CTVersionReq::parse(s).unwrap()
+}
+
+#[test]
+fn version_parse() {
+    let version = v("0.1.0-beta.1");
+    assert_eq!((0, 1, 0), (version.major, version.minor, version.patch));
+    assert_eq!(
+        vec![CTPreId::Alpha("beta".to_string()), CTPreId::Numeric(1)],
+        version.pre,
+    );
+    assert_eq!("0.1.0-beta.1", version.to_string());
+    assert_eq!(vec!["build", "5"], v("1.0.0+build.5").build);
+
+    assert!(CTVersion::parse("1.0").is_err());
+    assert!(CTVersion::parse("01.0.0").is_err());
+    assert!(CTVersion::parse("1.0.0-").is_err());
+}
+
+#[test]
+fn version_precedence() {
+    assert!(v("1.0.0-alpha") < v("1.0.0-alpha.1"));
+    assert!(v("1.0.0-alpha.1") < v("1.0.0"));
+    assert!(v("1.0.0-alpha.1") < v("1.0.0-alpha.beta"));
+    assert!(v("1.0.0-beta.2") < v("1.0.0-beta.11"));
+    assert!(v("0.9.9") < v("0.10.0"));
+    assert_eq!(v("1.0.0+a"), v("1.0.0+b"));
+}
+
+#[test]
+fn version_req_matches() {
+    let r = req(">=0.0.13, <0.1");
+    assert!(r.matches(&v("0.0.13")));
+    assert!(r.matches(&v("0.0.99")));
+    assert!(!r.matches(&v("0.0.12")));
+    assert!(!r.matches(&v("0.1.0")));
+
+    let r = req("^0.0.3");
+    assert!(r.matches(&v("0.0.3")));
+    assert!(!r.matches(&v("0.0.4")));
+
+    let r = req("~1.2");
+    assert!(r.matches(&v("1.2.0")));
+    assert!(r.matches(&v("1.2.9")));
+    assert!(!r.matches(&v("1.3.0")));
+
+    let r = req("1.*");
+    assert!(r.matches(&v("1.0.0")));
+    assert!(r.matches(&v("1.99.0")));
+    assert!(!r.matches(&v("2.0.0")));
+
+    assert!(CTVersionReq::parse("=>1.0").is_err());
+}
+
+#[test]
+fn version_req_pre_release_opt_in() {
+    // pre-releases only match a comparator on the same major.minor.patch
+    let r = req(">=0.1.0-beta.1");
+    assert!(r.matches(&v("0.1.0-beta.2")));
+    assert!(r.matches(&v("0.1.0")));
+    assert!(!r.matches(&v("0.2.0-alpha")));
+
+    assert!(!req(">=0.1.0").matches(&v("0.2.0-alpha")));
+}
+
+#[test]
+fn padded_version_compare() {
+    assert_eq!(v("1.70.0"), parse_padded_version("1.70").unwrap());
+    assert!(version_at_least("1.7.0-stable", "1.7"));
+    assert!(version_at_least("0.1.79", "0.1"));
+    assert!(!version_at_least("0.1.79", "0.2"));
+    assert!(!version_at_least("", "1"));
+}
+
+#[test]
+fn tool_version_reqs() {
+    let at_least = parse_min_version("1.7").unwrap();
+    assert!(tool_version_matches("1.7.0-stable", &at_least));
+    assert!(tool_version_matches("2.0.0", &at_least));
+    assert!(!tool_version_matches("1.6.9", &at_least));
+
+    let range = parse_min_version(">=3, <4").unwrap();
+    assert!(tool_version_matches("3.3.1", &range));
+    assert!(!tool_version_matches("4.0.0", &range));
+    assert!(!tool_version_matches("", &range));
+
+    assert!(parse_min_version("!=3").is_err());
+}
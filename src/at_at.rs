@@ -11,6 +11,12 @@ const CR: u8 = 13;
 /// '@' character
 const AT: u8 = 64;
 
+/// space
+const SP: u8 = 32;
+
+/// horizontal tab
+const TAB: u8 = 9;
+
 /// Type returned from AtAt parsing.
 #[derive(Debug)]
 pub enum AtAtParseItem {
@@ -25,7 +31,7 @@ enum State {
     /// middle of not @@ characters
     Waiting,
 
-    /// at line start - look for @
+    /// at line start (or its indentation) - look for @
     LineStart,
 
     /// we found an '@' at line-start - gather a name
@@ -96,7 +102,7 @@ impl<R: Read> AtAtParser<R> {
                 State::LineStart => {
                     if c == AT {
                         State::GatherName(Vec::new())
-                    } else if c == LF || c == CR {
+                    } else if c == LF || c == CR || c == SP || c == TAB {
                         State::LineStart
                     } else {
                         State::Waiting
@@ -136,3 +142,73 @@ impl<R: Read> AtAtParser<R> {
         Some(out)
     }
 }
+
+/// Remove all `@key@ value @@` items with the given key from AtAt encoded
+/// text. Any remaining content on the terminating line is removed as well.
+pub fn strip_key(src: &str, key: &str) -> String {
+    let mut out = String::new();
+    let mut skipping = false;
+
+    for line in src.split_inclusive('\n') {
+        if skipping {
+            if line.contains("@@") {
+                skipping = false;
+            }
+            continue;
+        }
+
+        if let Some(rest) =
+            line.trim_start_matches([' ', '\t']).strip_prefix('@')
+        {
+            if let Some(idx) = rest.find('@') {
+                if rest[..idx].trim() == key {
+                    skipping = !rest[idx + 1..].contains("@@");
+                    continue;
+                }
+            }
+        }
+
+        out.push_str(line);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(src: &str) -> Vec<(String, String)> {
+        let mut parser = AtAtParser::new(src.as_bytes());
+        let mut out = Vec::new();
+        while let Some(items) = parser.parse() {
+            for item in items {
+                if let AtAtParseItem::KeyValue(k, v) = item {
+                    out.push((k, v));
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn indented_keys() {
+        let src = "/*\n@a@ 1 @@\n    @b@\n    2\n    @@\n\t@c@ 3 @@\nx @d@ 4 @@\n*/\n";
+        assert_eq!(
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "2".to_string()),
+                ("c".to_string(), "3".to_string()),
+            ],
+            parse_all(src),
+        );
+        assert_eq!(
+            "/*\n@a@ 1 @@\n\t@c@ 3 @@\nx @d@ 4 @@\n*/\n",
+            strip_key(src, "b"),
+        );
+        assert_eq!(
+            "/*\n@a@ 1 @@\n    @b@\n    2\n    @@\nx @d@ 4 @@\n*/\n",
+            strip_key(src, "c"),
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Project-wide cargo-task configuration.
+//!
+//! Loaded from `.cargo-task/config.atat`, falling back to the
+//! `[package.metadata.cargo-task]` (or `[workspace.metadata.cargo-task]`)
+//! table of the project Cargo.toml for any unset settings.
+
+use crate::*;
+use std::path::Path;
+
+/// Project-wide cargo-task settings.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// default rust edition for script tasks
+    pub edition: Option<String>,
+
+    /// cargo profile to build tasks with
+    pub profile: Option<String>,
+
+    /// cargo-task target dir (relative to the project dir)
+    pub target: Option<String>,
+
+    /// colored log output
+    pub color: Option<bool>,
+
+    /// cargo build parallelism (`--jobs`)
+    pub jobs: Option<String>,
+
+    /// install missing `@ct-requires-tools@` tools instead of failing
+    /// `true`, or `managed` to install into the cargo-task managed root
+    pub install_tools: Option<String>,
+
+    /// tasks to run when no task list is specified
+    /// replaces any `@ct-default@` tasks
+    pub default_tasks: Option<Vec<String>>,
+
+    /// environment variables injected into every task
+    pub env: Vec<(String, String)>,
+
+    /// `config.atat` keys this version does not know (a newer
+    /// cargo-task may), warned about once when the runner loads
+    pub unknown_keys: Vec<String>,
+}
+
+impl Config {
+    /// fill any unset settings from another config
+    fn or(mut self, other: Config) -> Self {
+        self.edition = self.edition.or(other.edition);
+        self.profile = self.profile.or(other.profile);
+        self.target = self.target.or(other.target);
+        self.color = self.color.or(other.color);
+        self.jobs = self.jobs.or(other.jobs);
+        self.install_tools = self.install_tools.or(other.install_tools);
+        self.default_tasks = self.default_tasks.or(other.default_tasks);
+        for (name, value) in other.env {
+            if !self.env.iter().any(|(n, _)| n == &name) {
+                self.env.push((name, value));
+            }
+        }
+        self
+    }
+}
+
+/// Load the project config for a cargo-task work dir.
+pub fn load_config(work_dir: &Path) -> Config {
+    let mut config_atat = work_dir.join(CARGO_TASK_DIR);
+    config_atat.push("config.atat");
+
+    let cargo_toml = std::fs::read_to_string(work_dir.join("Cargo.toml"))
+        .map(|content| {
+            parse_cargo_toml(&content, "[package.metadata.cargo-task]").or(
+                parse_cargo_toml(&content, "[workspace.metadata.cargo-task]"),
+            )
+        })
+        .unwrap_or_default();
+
+    match std::fs::File::open(&config_atat) {
+        Ok(file) => parse_config_atat(file).or(cargo_toml),
+        Err(_) => cargo_toml,
+    }
+}
+
+/// parse `.cargo-task/config.atat` AtAt directives
+fn parse_config_atat(file: std::fs::File) -> Config {
+    let mut config = Config::default();
+
+    let mut parser = at_at::AtAtParser::new(file);
+    while let Some(items) = parser.parse() {
+        for item in items {
+            if let at_at::AtAtParseItem::KeyValue(k, v) = item {
+                match k.as_str() {
+                    "ct-edition" => {
+                        config.edition = Some(v);
+                    }
+                    "ct-profile" => {
+                        config.profile = Some(v);
+                    }
+                    "ct-target" => {
+                        config.target = Some(v);
+                    }
+                    "ct-color" => {
+                        config.color = Some(v == "true");
+                    }
+                    "ct-jobs" => {
+                        config.jobs = Some(v);
+                    }
+                    "ct-install-tools" => {
+                        config.install_tools = Some(v);
+                    }
+                    "ct-default-tasks" => {
+                        config.default_tasks = Some(
+                            v.split_whitespace()
+                                .map(|t| t.to_string())
+                                .collect(),
+                        );
+                    }
+                    "ct-env" => {
+                        for line in v.lines() {
+                            if let Some(kv) = parse_env_line(line) {
+                                config.env.push(kv);
+                            }
+                        }
+                    }
+                    _ => config.unknown_keys.push(k),
+                }
+            }
+        }
+    }
+
+    config
+}
+
+/// parse a `NAME=value` line, ignoring blank and `#` comment lines
+fn parse_env_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    match line.find('=') {
+        Some(idx) => Some((
+            line[..idx].trim().to_string(),
+            line[idx + 1..].trim().to_string(),
+        )),
+        None => ct_fatal!("no '=' found in config env '{}'", line),
+    }
+}
+
+/// parse a Cargo.toml `metadata.cargo-task` table (and its `env` sub-table)
+fn parse_cargo_toml(content: &str, section: &str) -> Config {
+    let mut config = Config::default();
+    let env_section = format!("{}.env]", &section[..section.len() - 1]);
+
+    let default_tasks =
+        workspace::toml_str_array(content, section, "default-tasks");
+    if !default_tasks.is_empty() {
+        config.default_tasks = Some(default_tasks);
+    }
+
+    let mut cur_section = "";
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            cur_section = line;
+            continue;
+        }
+        if cur_section != section && cur_section != env_section {
+            continue;
+        }
+        let idx = match line.find('=') {
+            Some(idx) if !line.starts_with('#') => idx,
+            _ => continue,
+        };
+        let key = line[..idx].trim().trim_matches('"');
+        let value = line[idx + 1..].trim();
+        let value = match value.strip_prefix('"') {
+            Some(value) => value.split('"').next().unwrap_or_default(),
+            None => value.split('#').next().unwrap_or_default().trim(),
+        }
+        .to_string();
+        if cur_section == env_section {
+            config.env.push((key.to_string(), value));
+            continue;
+        }
+        match key {
+            "edition" => config.edition = Some(value),
+            "profile" => config.profile = Some(value),
+            "target-dir" => config.target = Some(value),
+            "color" => config.color = Some(value == "true"),
+            "jobs" => config.jobs = Some(value),
+            "install-tools" => config.install_tools = Some(value),
+            _ => (),
+        }
+    }
+
+    config
+}
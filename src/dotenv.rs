@@ -0,0 +1,223 @@
+//! Dotenv-style environment file parsing.
+//!
+//! - `NAME=value` lines, with an optional leading `export `.
+//! - `#` comments, on their own line or after unquoted values.
+//! - `'single quoted'` values are taken literally.
+//! - `"double quoted"` values may span lines, support `\n`, `\t`,
+//!   `\"`, `\\` and `\$` escapes, and `${VAR}` / `$VAR` interpolation.
+//! - unquoted values are trimmed and support interpolation.
+
+/// Parse dotenv file content into (name, value) pairs.
+/// Interpolated variables are resolved with `lookup` first,
+/// then from variables defined earlier in the file.
+pub fn parse<F: Fn(&str) -> Option<String>>(
+    content: &str,
+    lookup: F,
+) -> Result<Vec<(String, String)>, String> {
+    let mut out: Vec<(String, String)> = Vec::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((line_no, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+
+        let idx = match line.find('=') {
+            Some(idx) => idx,
+            None => {
+                return Err(format!(
+                    "line {}: expected NAME=value",
+                    line_no + 1
+                ))
+            }
+        };
+        let name = line[..idx].trim().to_string();
+        if name.is_empty()
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(format!(
+                "line {}: invalid variable name '{}'",
+                line_no + 1,
+                name,
+            ));
+        }
+
+        let lookup = |n: &str| {
+            lookup(n).or_else(|| {
+                out.iter()
+                    .rev()
+                    .find(|(k, _)| k == n)
+                    .map(|(_, v)| v.clone())
+            })
+        };
+
+        let raw = line[idx + 1..].trim_start();
+        let value = if let Some(raw) = raw.strip_prefix('\'') {
+            match raw.find('\'') {
+                Some(end) => raw[..end].to_string(),
+                None => {
+                    return Err(format!(
+                        "line {}: unterminated single quote",
+                        line_no + 1
+                    ))
+                }
+            }
+        } else if let Some(raw) = raw.strip_prefix('"') {
+            // gather lines until the closing quote
+            let mut raw = raw.to_string();
+            while find_close_quote(&raw).is_none() {
+                match lines.next() {
+                    Some((_, next)) => {
+                        raw.push('\n');
+                        raw.push_str(next);
+                    }
+                    None => {
+                        return Err(format!(
+                            "line {}: unterminated double quote",
+                            line_no + 1
+                        ))
+                    }
+                }
+            }
+            let end = find_close_quote(&raw).unwrap();
+            expand(&raw[..end], true, &lookup)
+        } else {
+            let raw = match raw.find(" #") {
+                Some(idx) => &raw[..idx],
+                None => raw,
+            };
+            expand(raw.trim_end(), false, &lookup)
+        };
+
+        out.push((name, value));
+    }
+
+    Ok(out)
+}
+
+/// find the index of the unescaped closing double quote
+fn find_close_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(idx),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// process escapes (if `escapes`) and `${VAR}` / `$VAR` interpolation
+fn expand<F: Fn(&str) -> Option<String>>(
+    s: &str,
+    escapes: bool,
+    lookup: &F,
+) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if escapes => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            },
+            '$' => {
+                let mut name = String::new();
+                if chars.peek() == Some(&'{') {
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            break;
+                        }
+                        name.push(c);
+                    }
+                } else {
+                    while let Some(c) = chars.peek() {
+                        if !c.is_ascii_alphanumeric() && *c != '_' {
+                            break;
+                        }
+                        name.push(*c);
+                        chars.next();
+                    }
+                    if name.is_empty() {
+                        out.push('$');
+                        continue;
+                    }
+                }
+                out.push_str(&lookup(&name).unwrap_or_default());
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(content: &str) -> Vec<(String, String)> {
+        parse(content, |n| match n {
+            "HOME" => Some("/home/me".to_string()),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    fn kv(k: &str, v: &str) -> (String, String) {
+        (k.to_string(), v.to_string())
+    }
+
+    #[test]
+    fn parse_values() {
+        let vars = parse_ok(
+            r#"
+# comment
+export A=plain value  # trailing comment
+B='single $HOME \n'
+C="double\t\"q\""
+D="multi
+line"
+"#,
+        );
+        assert_eq!(
+            vec![
+                kv("A", "plain value"),
+                kv("B", "single $HOME \\n"),
+                kv("C", "double\t\"q\""),
+                kv("D", "multi\nline"),
+            ],
+            vars,
+        );
+    }
+
+    #[test]
+    fn parse_interpolation() {
+        let vars = parse_ok("A=${HOME}/a\nB=$A/b\nC=\"\\$A $MISSING-\"\n");
+        assert_eq!(
+            vec![
+                kv("A", "/home/me/a"),
+                kv("B", "/home/me/a/b"),
+                kv("C", "$A -"),
+            ],
+            vars,
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("NO_EQUALS", |_| None).is_err());
+        assert!(parse("BAD-NAME=1", |_| None).is_err());
+        assert!(parse("A='open", |_| None).is_err());
+        assert!(parse("A=\"open\nstill open", |_| None).is_err());
+    }
+}
@@ -6,6 +6,12 @@ use std::{
     path::{Path, PathBuf},
 };
 
+/// Rust edition for script tasks if not otherwise specified.
+const DEFAULT_EDITION: &str = "2018";
+
+/// Cargo profile for building tasks if not otherwise specified.
+const DEFAULT_PROFILE: &str = "release";
+
 fn set_env<N: AsRef<OsStr>, V: AsRef<OsStr>>(n: N, v: V) {
     std::env::set_var(n, v);
 }
@@ -28,6 +34,23 @@ fn clear() {
 /// Gather understanding of our cargo-task location.
 /// Translate it all into environment variables that CTEnv can read.
 pub fn load() -> Result<(), &'static str> {
+    // user-specified overrides - read these before we clear CT_ vars
+    let target_override = std::env::var_os("CT_TARGET");
+    let shared_target = std::env::var_os("CT_SHARED_TARGET").is_some();
+    let edition_override = std::env::var("CT_EDITION").ok();
+    let mut profile_override = std::env::var("CT_PROFILE").ok();
+    let no_color = std::env::var_os("CT_NO_COLOR").is_some();
+    let mut jobs = std::env::var("CT_JOBS").ok();
+    let install_tools = std::env::var("CT_INSTALL_TOOLS").ok();
+    let prebuild = std::env::var_os("CT_PREBUILD").is_some();
+    let mut offline = std::env::var_os("CT_OFFLINE").is_some();
+    let mut locked = std::env::var_os("CT_LOCKED").is_some();
+    let mut frozen = std::env::var_os("CT_FROZEN").is_some();
+    let mut each_member = false;
+    // set when invoked from a task via CTEnv::run_task
+    let task_stack = std::env::var_os("CT_TASK_STACK");
+    let return_file = std::env::var_os("CT_RETURN_FILE");
+
     clear();
 
     // cargo binary path
@@ -37,22 +60,74 @@ pub fn load() -> Result<(), &'static str> {
     set_env("CARGO", cargo_path);
 
     // work_dir
-    let work_dir = find_cargo_task_work_dir()?;
+    // (innermost first)
+    let work_dirs = find_cargo_task_work_dirs()?;
+    let work_dir = work_dirs[0].clone();
     set_env("CT_WORK_DIR", &work_dir);
 
     // cargo task path
-    let mut cargo_task_path = work_dir;
+    let mut cargo_task_path = work_dir.clone();
     cargo_task_path.push(CARGO_TASK_DIR);
     set_env("CT_PATH", &cargo_task_path);
 
+    // project config - env vars and cli flags take precedence
+    let config = config::load_config(&work_dir);
+    let default_edition = edition_override
+        .or(config.edition)
+        .unwrap_or_else(|| DEFAULT_EDITION.to_string());
+    if profile_override.is_none() {
+        profile_override = config.profile;
+    }
+    if jobs.is_none() {
+        jobs = config.jobs;
+    }
+    match (no_color, config.color) {
+        (true, _) | (false, Some(false)) => set_env("CT_NO_COLOR", "1"),
+        (false, Some(true)) => set_env("CT_WITH_COLOR", "1"),
+        (false, None) => (),
+    }
+    for key in config.unknown_keys.iter() {
+        ct_warn!("ignoring unrecognized config.atat key '{}'", key);
+    }
+
     // cargo task target dir
     let mut cargo_task_target = cargo_task_path.clone();
     cargo_task_target.push("target");
-    if let Some(target) = std::env::var_os("CT_TARGET") {
+    if let Some(target) = config.target {
+        cargo_task_target = work_dir.join(target);
+    }
+    if shared_target {
+        set_env("CT_SHARED_TARGET", "1");
+        cargo_task_target = workspace::project_target_dir(&work_dir);
+        cargo_task_target.push("cargo-task");
+    }
+    if let Some(target) = &target_override {
         cargo_task_target = PathBuf::from(target);
     }
     set_env("CT_TARGET", &cargo_task_target);
 
+    // build settings
+    set_env("CT_EDITION", &default_edition);
+    if let Some(profile) = &profile_override {
+        set_env("CT_PROFILE", profile);
+    }
+    if prebuild {
+        set_env("CT_PREBUILD", "1");
+    }
+    if let Some(jobs) = &jobs {
+        set_env("CT_JOBS", jobs);
+    }
+    let install_tools = match (install_tools, config.install_tools.as_deref()) {
+        (Some(v), _) => Some(v),
+        (None, Some(v @ ("true" | "managed"))) => Some(v.to_string()),
+        _ => None,
+    };
+    match install_tools.as_deref() {
+        Some("managed") => set_env("CT_INSTALL_TOOLS", "managed"),
+        Some(_) => set_env("CT_INSTALL_TOOLS", "1"),
+        None => (),
+    }
+
     // cli arguments
     let mut tasks = Vec::new();
     let mut args = Vec::new();
@@ -67,6 +142,14 @@ pub fn load() -> Result<(), &'static str> {
         }
         if found_sep {
             args.push(arg);
+        } else if arg == "--offline" {
+            offline = true;
+        } else if arg == "--locked" {
+            locked = true;
+        } else if arg == "--frozen" {
+            frozen = true;
+        } else if arg == "--each-member" {
+            each_member = true;
         } else {
             tasks.push(arg);
         }
@@ -74,35 +157,167 @@ pub fn load() -> Result<(), &'static str> {
     set_env("CT_TASKS", tasks.join(" "));
     set_env("CT_ARGS", args.join(" "));
 
-    // load cargo-task tasks
-    let tasks = enumerate_task_metadata(&cargo_task_path);
+    // cargo network / lock file flags
+    if offline {
+        set_env("CT_OFFLINE", "1");
+    }
+    if locked {
+        set_env("CT_LOCKED", "1");
+    }
+    if frozen {
+        set_env("CT_FROZEN", "1");
+    }
+
+    // run the task list per workspace member
+    if each_member {
+        set_env("CT_EACH_MEMBER", "1");
+    }
+
+    // nested task runner invocation
+    if let Some(task_stack) = &task_stack {
+        set_env("CT_TASK_STACK", task_stack);
+    }
+    if let Some(return_file) = &return_file {
+        set_env("CT_RETURN_FILE", return_file);
+    }
+
+    // load cargo-task tasks from all levels
+    // inner tasks override outer tasks with the same name,
+    // overridden outer tasks are available as `namespace:name`,
+    // task deps between tasks of one level always resolve within it
+    let mut tasks = BTreeMap::new();
+    let mut namespaces = Vec::new();
+    let mut targets = Vec::new();
+    for level_dir in work_dirs.iter() {
+        let namespace = level_namespace(level_dir, &namespaces);
+        let target = if level_dir == &work_dir {
+            cargo_task_target.clone()
+        } else {
+            outer_level_target(
+                level_dir,
+                &namespace,
+                target_override.as_deref(),
+                shared_target,
+                &targets,
+            )
+        };
+        let level = enumerate_task_metadata(
+            level_dir,
+            &namespace,
+            &target,
+            &default_edition,
+            profile_override.as_deref(),
+        );
+        namespaces.push(namespace);
+        targets.push(target);
+        let mut level_names = BTreeMap::new();
+        for (name, task) in level.iter() {
+            let full_name = if tasks.contains_key(name) {
+                format!("{}:{}", task.namespace, name)
+            } else {
+                name.clone()
+            };
+            level_names.insert(name.clone(), full_name);
+        }
+        for (name, mut task) in level {
+            task.name = level_names[&name].clone();
+            // deps on tasks of the same level stay within that level,
+            // even if an inner level overrides a task with the same name
+            for dep in task.task_deps.iter_mut() {
+                if let Some(full_name) = level_names.get(dep) {
+                    *dep = full_name.clone();
+                }
+            }
+            tasks.insert(task.name.clone(), task);
+        }
+    }
+    if let Some(default_tasks) = &config.default_tasks {
+        for name in default_tasks.iter() {
+            if !tasks.contains_key(name) {
+                ct_fatal!("config default task '{}' not found", name);
+            }
+        }
+        for (name, task) in tasks.iter_mut() {
+            task.default = default_tasks.contains(name);
+        }
+    }
     for (_, task) in tasks {
-        let path_name = format!("CT_TASK_{}_PATH", task.name);
+        // task names may contain `-`, or `:` / `/` if namespaced
+        let env_name = _cargo_task_util::env_name_encode(&task.name);
+        let path_name = format!("CT_TASK_{}_PATH", env_name);
         set_env(&path_name, &task.path);
+        let wd_name = format!("CT_TASK_{}_WORK_DIR", env_name);
+        set_env(&wd_name, &task.work_dir);
+        let ns_name = format!("CT_TASK_{}_NAMESPACE", env_name);
+        set_env(&ns_name, &task.namespace);
+        let target_name = format!("CT_TASK_{}_TARGET", env_name);
+        set_env(&target_name, &task.target_dir);
         if task.is_script {
-            let script_name = format!("CT_TASK_{}_IS_SCRIPT", task.name);
+            let script_name = format!("CT_TASK_{}_IS_SCRIPT", env_name);
             set_env(&script_name, "1");
         }
         if let Some(min_version) = &task.min_version {
-            let mv_name = format!("CT_TASK_{}_MIN_VER", task.name);
+            let mv_name = format!("CT_TASK_{}_MIN_VER", env_name);
             set_env(&mv_name, min_version);
         }
+        if let Some(version_req) = &task.version_req {
+            let vr_name = format!("CT_TASK_{}_VERSION_REQ", env_name);
+            set_env(&vr_name, version_req);
+        }
+        if let Some(rust_version) = &task.rust_version {
+            let rv_name = format!("CT_TASK_{}_RUST_VERSION", env_name);
+            set_env(&rv_name, rust_version);
+        }
+        if let Some(toolchain) = &task.toolchain {
+            let tc_name = format!("CT_TASK_{}_TOOLCHAIN", env_name);
+            set_env(&tc_name, toolchain);
+        }
         if task.default {
-            let def_name = format!("CT_TASK_{}_DEFAULT", task.name);
+            let def_name = format!("CT_TASK_{}_DEFAULT", env_name);
             set_env(&def_name, "1");
         }
         if task.bootstrap {
-            let bs_name = format!("CT_TASK_{}_BOOTSTRAP", task.name);
+            let bs_name = format!("CT_TASK_{}_BOOTSTRAP", env_name);
             set_env(&bs_name, "1");
         }
         if !task.help.is_empty() {
-            let def_name = format!("CT_TASK_{}_HELP", task.name);
+            let def_name = format!("CT_TASK_{}_HELP", env_name);
             set_env(&def_name, &task.help);
         }
+        let ed_name = format!("CT_TASK_{}_EDITION", env_name);
+        set_env(&ed_name, &task.edition);
+        let prof_name = format!("CT_TASK_{}_PROFILE", env_name);
+        set_env(&prof_name, &task.profile);
         if let Some(cargo_deps) = task.cargo_deps {
-            let deps_name = format!("CT_TASK_{}_CARGO_DEPS", task.name);
+            let deps_name = format!("CT_TASK_{}_CARGO_DEPS", env_name);
             set_env(&deps_name, cargo_deps);
         }
+        if let Some(cargo_toml) = task.cargo_toml {
+            let toml_name = format!("CT_TASK_{}_CARGO_TOML", env_name);
+            set_env(&toml_name, cargo_toml);
+        }
+        if let Some(build_rs) = task.build_rs {
+            let brs_name = format!("CT_TASK_{}_BUILD_RS", env_name);
+            set_env(&brs_name, build_rs);
+        }
+        if !task.project_crates.is_empty() {
+            let pc_name = format!("CT_TASK_{}_PROJECT_CRATES", env_name);
+            set_env(&pc_name, task.project_crates.join(" "));
+        }
+        if !task.features.is_empty() {
+            let feat_name = format!("CT_TASK_{}_FEATURES", env_name);
+            set_env(&feat_name, task.features.join(" "));
+        }
+        if !task.env_files.is_empty() {
+            let ef_name = format!("CT_TASK_{}_ENV_FILES", env_name);
+            set_env(&ef_name, task.env_files.join(" "));
+        }
+        let scope_name = format!("CT_TASK_{}_ENV_SCOPE", env_name);
+        set_env(&scope_name, &task.env_scope);
+        if !task.requires_tools.is_empty() {
+            let rt_name = format!("CT_TASK_{}_REQUIRES_TOOLS", env_name);
+            set_env(&rt_name, task.requires_tools.join(" "));
+        }
         let mut task_deps = "".to_string();
         for task_dep in task.task_deps.iter() {
             if !task_deps.is_empty() {
@@ -111,7 +326,7 @@ pub fn load() -> Result<(), &'static str> {
             task_deps.push_str(task_dep);
         }
         if !task_deps.is_empty() {
-            let deps_name = format!("CT_TASK_{}_TASK_DEPS", task.name);
+            let deps_name = format!("CT_TASK_{}_TASK_DEPS", env_name);
             set_env(&deps_name, &task_deps);
         }
     }
@@ -120,38 +335,109 @@ pub fn load() -> Result<(), &'static str> {
 }
 
 /// Searches up the directories from the current dir,
-/// looking for a directory containing a '.cargo-task' directory.
-fn find_cargo_task_work_dir() -> Result<PathBuf, &'static str> {
+/// collecting all directories containing a '.cargo-task' directory.
+/// Stops at the repository root (a directory containing '.git')
+/// once at least one has been found. Results are innermost first.
+fn find_cargo_task_work_dirs() -> Result<Vec<PathBuf>, &'static str> {
     const E: &str = "failed to find .cargo-task dir";
     let mut cargo_task_path = std::env::current_dir().map_err(|_| E)?;
+    let mut out = Vec::new();
 
     loop {
+        let mut is_repo_root = false;
         for item in std::fs::read_dir(&cargo_task_path)
             .map_err(|_| E)?
             .flatten()
         {
+            if item.file_name() == ".git" {
+                is_repo_root = true;
+            }
             if !item.file_type().map_err(|_| E)?.is_dir() {
                 continue;
             }
             if item.file_name() == CARGO_TASK_DIR {
-                return Ok(cargo_task_path);
+                out.push(cargo_task_path.clone());
             }
         }
 
-        if !cargo_task_path.pop() {
+        if (is_repo_root && !out.is_empty()) || !cargo_task_path.pop() {
             break;
         }
     }
 
-    Err(E)
+    if out.is_empty() {
+        return Err(E);
+    }
+
+    Ok(out)
+}
+
+/// The namespace of a task level: its directory name, qualified with
+/// parent directory names (`repo/app`) if an inner level already uses it.
+fn level_namespace(work_dir: &Path, taken: &[String]) -> String {
+    let mut parts = Vec::new();
+    for dir in work_dir.ancestors() {
+        let name = match dir.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => break,
+        };
+        parts.insert(0, name);
+        let namespace = parts.join("/");
+        if !taken.contains(&namespace) {
+            return namespace;
+        }
+    }
+    work_dir.to_string_lossy().to_string()
+}
+
+/// The build target dir for the tasks of an outer level, the same as
+/// when running `cargo task` in that directory, so they are not rebuilt.
+/// A relative `CT_TARGET` is resolved against the outer level directory.
+/// If that is already the target of an inner level (e.g. an absolute
+/// `CT_TARGET`, or a shared project target dir), a `ct-outer/<namespace>`
+/// subdirectory of it is used instead.
+fn outer_level_target(
+    work_dir: &Path,
+    namespace: &str,
+    target_override: Option<&OsStr>,
+    shared_target: bool,
+    taken: &[PathBuf],
+) -> PathBuf {
+    let mut target = work_dir.join(CARGO_TASK_DIR);
+    target.push("target");
+    if let Some(config_target) = config::load_config(work_dir).target {
+        target = work_dir.join(config_target);
+    }
+    if shared_target {
+        target = workspace::project_target_dir(work_dir);
+        target.push("cargo-task");
+    }
+    if let Some(target_override) = target_override {
+        target = work_dir.join(target_override);
+    }
+    if taken.contains(&target) {
+        target.push("ct-outer");
+        target.push(namespace);
+    }
+    target
 }
 
 /// Searches CARGO_TASK_DIR for defined tasks, and loads up metadata.
+/// Script tasks without an `@ct-edition@` use `default_edition`.
+/// If `profile_override` is set, it takes precedence over `@ct-profile@`.
 fn enumerate_task_metadata<P: AsRef<Path>>(
-    cargo_task_path: P,
+    work_dir: P,
+    namespace: &str,
+    target_dir: &Path,
+    default_edition: &str,
+    profile_override: Option<&str>,
 ) -> BTreeMap<String, CTTaskMeta> {
     let mut out = BTreeMap::new();
 
+    let work_dir = work_dir.as_ref().to_owned();
+    let mut cargo_task_path = work_dir.clone();
+    cargo_task_path.push(CARGO_TASK_DIR);
+
     for item in std::fs::read_dir(&cargo_task_path)
         .expect("failed to read directory")
         .flatten()
@@ -169,15 +455,33 @@ fn enumerate_task_metadata<P: AsRef<Path>>(
         if file_type.is_file() && file_name.ends_with(".ct.rs") {
             let path = item.path();
             let meta = ct_check_fatal!(parse_metadata(&path));
+            let profile = task_profile(&meta, profile_override);
             let meta = CTTaskMeta {
                 name: file_name[..file_name.len() - 6].to_string(),
                 is_script: true,
                 min_version: meta.min_version,
+                version_req: meta.version_req,
+                rust_version: meta.rust_version,
+                toolchain: meta.toolchain,
                 path,
+                work_dir: work_dir.clone(),
+                namespace: namespace.to_string(),
+                target_dir: target_dir.to_owned(),
                 default: meta.default,
                 bootstrap: meta.bootstrap,
                 help: meta.help,
+                edition: meta
+                    .edition
+                    .unwrap_or_else(|| default_edition.to_string()),
+                profile,
                 cargo_deps: meta.cargo_deps,
+                cargo_toml: meta.cargo_toml,
+                build_rs: meta.build_rs,
+                project_crates: meta.project_crates,
+                features: meta.features,
+                env_files: meta.env_files,
+                env_scope: meta.env_scope,
+                requires_tools: meta.requires_tools,
                 task_deps: meta.task_deps,
             };
             out.insert(meta.name.clone(), meta);
@@ -190,15 +494,40 @@ fn enumerate_task_metadata<P: AsRef<Path>>(
             if meta.cargo_deps.is_some() {
                 ct_fatal!("@ct-cargo-deps@ are illegal in directory-style task crates - just specify your deps in your Cargo.toml file");
             }
+            if meta.cargo_toml.is_some() {
+                ct_fatal!("@ct-cargo-toml@ is illegal in directory-style task crates - just edit your Cargo.toml file");
+            }
+            if meta.build_rs.is_some() {
+                ct_fatal!("@ct-build-rs@ is illegal in directory-style task crates - just add a build.rs file to your crate");
+            }
+            if meta.edition.is_some() {
+                ct_fatal!("@ct-edition@ is illegal in directory-style task crates - just specify the edition in your Cargo.toml file");
+            }
+            let profile = task_profile(&meta, profile_override);
             let meta = CTTaskMeta {
                 name: file_name,
                 is_script: false,
                 min_version: meta.min_version,
+                version_req: meta.version_req,
+                rust_version: meta.rust_version,
+                toolchain: meta.toolchain,
                 path,
+                work_dir: work_dir.clone(),
+                namespace: namespace.to_string(),
+                target_dir: target_dir.to_owned(),
                 default: meta.default,
                 bootstrap: meta.bootstrap,
                 help: meta.help,
+                edition: "".to_string(),
+                profile,
                 cargo_deps: None,
+                cargo_toml: None,
+                build_rs: None,
+                project_crates: meta.project_crates,
+                features: meta.features,
+                env_files: meta.env_files,
+                env_scope: meta.env_scope,
+                requires_tools: meta.requires_tools,
                 task_deps: meta.task_deps,
             };
             out.insert(meta.name.clone(), meta);
@@ -208,11 +537,34 @@ fn enumerate_task_metadata<P: AsRef<Path>>(
     out
 }
 
+/// the cargo profile a task should be built with
+fn task_profile(meta: &Meta, profile_override: Option<&str>) -> String {
+    match profile_override {
+        Some(profile) => profile.to_string(),
+        None => meta
+            .profile
+            .clone()
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+    }
+}
+
 struct Meta {
     min_version: Option<String>,
+    version_req: Option<String>,
+    rust_version: Option<String>,
+    toolchain: Option<String>,
+    edition: Option<String>,
+    profile: Option<String>,
     default: bool,
     bootstrap: bool,
     cargo_deps: Option<String>,
+    cargo_toml: Option<String>,
+    build_rs: Option<String>,
+    project_crates: Vec<String>,
+    features: Vec<String>,
+    env_files: Vec<String>,
+    env_scope: String,
+    requires_tools: Vec<String>,
     task_deps: Vec<String>,
     help: String,
 }
@@ -221,9 +573,21 @@ impl Default for Meta {
     fn default() -> Self {
         Self {
             min_version: None,
+            version_req: None,
+            rust_version: None,
+            toolchain: None,
+            edition: None,
+            profile: None,
             default: false,
             bootstrap: false,
             cargo_deps: None,
+            cargo_toml: None,
+            build_rs: None,
+            project_crates: Vec::new(),
+            features: Vec::new(),
+            env_files: Vec::new(),
+            env_scope: "global".to_string(),
+            requires_tools: Vec::new(),
             task_deps: Vec::new(),
             help: "".to_string(),
         }
@@ -243,21 +607,90 @@ fn parse_metadata<P: AsRef<Path>>(path: P) -> Result<Meta, String> {
             if let at_at::AtAtParseItem::KeyValue(k, v) = item {
                 match k.as_str() {
                     "ct-min-version" => {
+                        CTVersion::parse(&v).map_err(|e| {
+                            format!(
+                                "{:?}: ct-min-version: {}",
+                                path.as_ref(),
+                                e
+                            )
+                        })?;
                         meta.min_version = Some(v);
                     }
-                    "ct-default" => {
-                        if v == "true" {
-                            meta.default = true;
-                        }
+                    "ct-version-req" => {
+                        CTVersionReq::parse(&v).map_err(|e| {
+                            format!(
+                                "{:?}: ct-version-req: {}",
+                                path.as_ref(),
+                                e
+                            )
+                        })?;
+                        meta.version_req = Some(v);
                     }
-                    "ct-bootstrap" => {
-                        if v == "true" {
-                            meta.bootstrap = true;
-                        }
+                    "ct-rust-version" => {
+                        parse_padded_version(&v).map_err(|e| {
+                            format!(
+                                "{:?}: ct-rust-version: {}",
+                                path.as_ref(),
+                                e
+                            )
+                        })?;
+                        meta.rust_version = Some(v);
+                    }
+                    "ct-toolchain" => {
+                        meta.toolchain = Some(v);
+                    }
+                    "ct-edition" => {
+                        meta.edition = Some(v);
+                    }
+                    "ct-profile" => {
+                        meta.profile = Some(v);
+                    }
+                    "ct-default" if v == "true" => {
+                        meta.default = true;
+                    }
+                    "ct-bootstrap" if v == "true" => {
+                        meta.bootstrap = true;
                     }
                     "ct-cargo-deps" => {
                         meta.cargo_deps = Some(v);
                     }
+                    "ct-cargo-toml" => {
+                        meta.cargo_toml = Some(v);
+                    }
+                    "ct-build-rs" => {
+                        meta.build_rs = Some(v);
+                    }
+                    "ct-use-project-crate" => {
+                        for name in v.split_whitespace() {
+                            meta.project_crates.push(name.to_string());
+                        }
+                    }
+                    "ct-features" => {
+                        for feature in v.split_whitespace() {
+                            meta.features.push(feature.to_string());
+                        }
+                    }
+                    "ct-env-file" => {
+                        for file in v.split_whitespace() {
+                            meta.env_files.push(file.to_string());
+                        }
+                    }
+                    "ct-env-scope" => match v.as_str() {
+                        "global" | "dependents" => meta.env_scope = v,
+                        _ => {
+                            return Err(format!(
+                                "{:?}: invalid ct-env-scope '{}', \
+                                expected 'global' or 'dependents'",
+                                path.as_ref(),
+                                v,
+                            ))
+                        }
+                    },
+                    "ct-requires-tools" => {
+                        for tool in v.split_whitespace() {
+                            meta.requires_tools.push(tool.to_string());
+                        }
+                    }
                     "ct-task-deps" => {
                         for dep in v.split_whitespace() {
                             meta.task_deps.push(dep.to_string());
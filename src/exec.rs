@@ -1,6 +1,7 @@
 use crate::*;
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
+    ffi::OsString,
     path::{Path, PathBuf},
 };
 
@@ -21,15 +22,25 @@ Have you run 'cargo task ct-init'?",
     // parse environment vars into env struct
     let mut env = _cargo_task_util::ct_env();
 
+    // any tasks that consume the rest of the task list as arguments?
+    task::check_arg_task(&env);
+
     ct_info!("cargo-task running...");
 
-    clean_build_workspace(&env);
-    let mut did_build_workspace = false;
+    // if we were invoked from a task via `CTEnv::run_task`
+    // the parent runner has already bootstrapped, and owns the workspace
+    let nested = std::env::var_os("CT_TASK_STACK").is_some();
+
+    if !nested {
+        clean_build_workspace(&env);
+    }
+    let mut did_build_workspace = HashSet::new();
+    let mut task_env = TaskEnv::new(&env);
 
     // check for bootstrap tasks
     let mut task_list = Vec::new();
     for (task, task_meta) in env.tasks.iter() {
-        if task_meta.bootstrap {
+        if task_meta.bootstrap && !nested {
             fill_task_deps(
                 &env,
                 &mut task_list,
@@ -47,10 +58,19 @@ Have you run 'cargo task ct-init'?",
                 // run ct-init to ensure our cargo_task_util crate is up-to-date
                 task::ct_init();
 
-                run_task(&env, &task, &mut did_build_workspace);
+                if let Err(e) = run_task(
+                    &env,
+                    &mut task_env,
+                    &task,
+                    &mut did_build_workspace,
+                    None,
+                ) {
+                    ct_fatal!("{}", e);
+                }
             }
         }
         ct_info!("reloading env post-bootstrap");
+        task_env.apply_bootstrap_exports(&env);
         if env_loader::load().is_err() {
             ct_fatal!(
                 r"ERROR: Could not find '{}' directory.
@@ -59,15 +79,136 @@ Have you run 'cargo task ct-init'?",
             );
         }
         clean_build_workspace(&env);
-        did_build_workspace = false;
+        did_build_workspace.clear();
 
         env = _cargo_task_util::ct_force_new_env();
+        task_env.reload_base(&env);
+    }
+
+    let task_list = resolve_task_list(&env, &env.task_list);
+
+    ct_info!("task order: {:?}", task_list);
+
+    // build all tasks up-front so cargo can build them in parallel
+    if std::env::var_os("CT_PREBUILD").is_some() {
+        task::ct_init();
+        prebuild_tasks(&env, &task_env, &task_list, &mut did_build_workspace);
+    }
+
+    if env.each_member {
+        run_each_member(
+            &env,
+            &mut task_env,
+            &task_list,
+            &mut did_build_workspace,
+        );
+    } else {
+        for task in task_list {
+            if !task::check_system_task(task.as_str(), &env) {
+                // run ct-init to ensure our cargo_task_util crate is up-to-date
+                task::ct_init();
+
+                if let Err(e) = run_task(
+                    &env,
+                    &mut task_env,
+                    &task,
+                    &mut did_build_workspace,
+                    None,
+                ) {
+                    ct_fatal!("{}", e);
+                }
+            }
+        }
+    }
+
+    if !nested {
+        clean_build_workspace(&env);
+    }
+    write_return_file(&task_env);
+
+    ct_info!("cargo-task complete : )");
+}
+
+/// when invoked from a task via `CTEnv::run_task`,
+/// hand our exports back to the calling task
+fn write_return_file(task_env: &TaskEnv) {
+    let return_file = match std::env::var_os("CT_RETURN_FILE") {
+        Some(return_file) => return_file,
+        None => return,
+    };
+    let mut out = String::new();
+    for e in task_env.exports.iter() {
+        out.push_str(&e.directive());
+    }
+    ct_check_fatal!(std::fs::write(return_file, out));
+}
+
+/// run the task list once per cargo workspace member
+/// system tasks are only run once, before any member tasks
+fn run_each_member(
+    env: &_cargo_task_util::CTEnv,
+    task_env: &mut TaskEnv,
+    task_list: &[String],
+    did_build_workspace: &mut HashSet<PathBuf>,
+) {
+    let root = workspace::workspace_root(&env.work_dir);
+    let members = workspace::workspace_members(&root);
+    if members.is_empty() {
+        ct_fatal!("no cargo workspace members found in {:?}", root);
+    }
+
+    // system tasks run once, up-front, the rest run for every member
+    let mut member_tasks = Vec::new();
+    for task in task_list.iter() {
+        if !task::check_system_task(task.as_str(), env) {
+            member_tasks.push(task);
+        }
+    }
+
+    // run ct-init to ensure our cargo_task_util crate is up-to-date
+    task::ct_init();
+
+    let mut status = Vec::new();
+    for member in members.iter() {
+        let mut member_status = "passed";
+        for task in member_tasks.iter() {
+            if let Err(e) =
+                run_task(env, task_env, task, did_build_workspace, Some(member))
+            {
+                ct_warn!("member '{}': {}", member.name, e);
+                member_status = "FAILED";
+                break;
+            }
+        }
+        status.push((member.name.as_str(), member_status));
+    }
+
+    let mut report = "member status:".to_string();
+    for (member, member_status) in status.iter() {
+        report.push_str(&format!("\n{:>22} - {}", member, member_status));
+    }
+    ct_info!("{}", report);
+
+    let failed = status
+        .iter()
+        .filter(|(_, s)| *s == "FAILED")
+        .map(|(m, _)| *m)
+        .collect::<Vec<_>>();
+    if !failed.is_empty() {
+        ct_fatal!("tasks failed for members {:?}", failed);
     }
+}
 
+/// resolve a task list with all task dependencies, in execution order
+/// if the list is empty, the default tasks are used
+pub(crate) fn resolve_task_list(
+    env: &_cargo_task_util::CTEnv,
+    tasks: &[String],
+) -> Vec<String> {
     // load up specified tasks
     let mut task_list = Vec::new();
-    for task in env.task_list.iter() {
-        fill_task_deps(&env, &mut task_list, task.to_string(), HashSet::new());
+    for task in tasks.iter() {
+        fill_task_deps(env, &mut task_list, task.to_string(), HashSet::new());
     }
 
     // if no specified tasks - load default tasks
@@ -75,7 +216,7 @@ Have you run 'cargo task ct-init'?",
         for (task, task_meta) in env.tasks.iter() {
             if task_meta.default {
                 fill_task_deps(
-                    &env,
+                    env,
                     &mut task_list,
                     task.to_string(),
                     HashSet::new(),
@@ -84,20 +225,7 @@ Have you run 'cargo task ct-init'?",
         }
     }
 
-    ct_info!("task order: {:?}", task_list);
-
-    for task in task_list {
-        if !task::check_system_task(task.as_str(), &env) {
-            // run ct-init to ensure our cargo_task_util crate is up-to-date
-            task::ct_init();
-
-            run_task(&env, &task, &mut did_build_workspace);
-        }
-    }
-
-    clean_build_workspace(&env);
-
-    ct_info!("cargo-task complete : )");
+    task_list
 }
 
 /// fill task deps
@@ -107,6 +235,7 @@ fn fill_task_deps(
     task: String,
     mut visited: HashSet<String>,
 ) {
+    let task = resolve_task_name(env, task);
     visited.insert(task.clone());
     if !env.tasks.contains_key(&task) {
         // this may be a psuedo task - add it, but don't check deps
@@ -116,7 +245,7 @@ fn fill_task_deps(
         return;
     }
     for dep in env.tasks.get(&task).unwrap().task_deps.iter() {
-        if visited.contains(dep) {
+        if visited.contains(&resolve_task_name(env, dep.to_string())) {
             ct_fatal!("circular task dependency within {:?}", visited);
         }
         fill_task_deps(env, task_list, dep.to_string(), visited.clone());
@@ -126,20 +255,81 @@ fn fill_task_deps(
     }
 }
 
-/// delete the cargo-task build workspace
+/// resolve a `namespace:name` task name to the key it is defined under
+/// if the task was not overridden by an inner task of the same name
+fn resolve_task_name(env: &_cargo_task_util::CTEnv, task: String) -> String {
+    if env.tasks.contains_key(&task) {
+        return task;
+    }
+    if let Some(idx) = task.rfind(':') {
+        let (namespace, name) = (&task[..idx], &task[idx + 1..]);
+        if let Some(task_meta) = env.tasks.get(name) {
+            if task_meta.namespace == namespace {
+                return name.to_string();
+            }
+        }
+    }
+    task
+}
+
+/// A `.cargo-task` directory tasks are defined in.
+/// Tasks from outer directories are built in their own target directory.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct TaskLevel {
+    /// the `.cargo-task` directory
+    pub cargo_task_path: PathBuf,
+
+    /// the build target directory for tasks in this directory
+    pub cargo_task_target: PathBuf,
+}
+
+/// the `.cargo-task` directory level a task is defined in
+pub(crate) fn task_level(
+    env: &_cargo_task_util::CTEnv,
+    task_meta: &_cargo_task_util::CTTaskMeta,
+) -> TaskLevel {
+    if task_meta.work_dir == env.work_dir {
+        return TaskLevel {
+            cargo_task_path: env.cargo_task_path.clone(),
+            cargo_task_target: env.cargo_task_target.clone(),
+        };
+    }
+    let mut cargo_task_path = task_meta.work_dir.clone();
+    cargo_task_path.push(CARGO_TASK_DIR);
+    TaskLevel {
+        cargo_task_path,
+        cargo_task_target: task_meta.target_dir.clone(),
+    }
+}
+
+/// delete the cargo-task build workspaces
 fn clean_build_workspace(env: &_cargo_task_util::CTEnv) {
     let mut ws = env.cargo_task_target.clone();
     ws.push("ct-workspace");
     let _ = std::fs::remove_dir_all(&ws);
+    for task_meta in env.tasks.values() {
+        let mut ws = task_level(env, task_meta).cargo_task_target;
+        ws.push("ct-workspace");
+        let _ = std::fs::remove_dir_all(&ws);
+    }
 }
 
-/// prep the cargo-task build workspace
-fn generate_build_workspace(env: &_cargo_task_util::CTEnv) {
+/// prep the cargo-task build workspace for a task level
+fn generate_build_workspace(env: &_cargo_task_util::CTEnv, level: &TaskLevel) {
     let mut all_tasks = Vec::new();
-    let mut ws = env.cargo_task_target.clone();
+    let mut ws = level.cargo_task_target.clone();
     ws.push("ct-workspace");
     ct_check_fatal!(std::fs::create_dir_all(&ws));
 
+    // copy in any committed lock file
+    let mut lock_src = level.cargo_task_path.clone();
+    lock_src.push("Cargo.lock");
+    if std::fs::metadata(&lock_src).is_ok() {
+        let mut lock_dest = ws.clone();
+        lock_dest.push("Cargo.lock");
+        ct_check_fatal!(std::fs::copy(&lock_src, &lock_dest));
+    }
+
     // copy in our cargo_task_util crate
     let mut ctu_src = env.cargo_task_path.clone();
     ctu_src.push("cargo_task_util");
@@ -151,7 +341,13 @@ fn generate_build_workspace(env: &_cargo_task_util::CTEnv) {
         }
     }
 
-    for (task, task_meta) in env.tasks.iter() {
+    let mut project_crates = BTreeMap::new();
+
+    for task_meta in env.tasks.values() {
+        if &task_level(env, task_meta) != level {
+            continue;
+        }
+        let task = task_meta.base_name();
         all_tasks.push(task);
 
         let mut task_dir = ws.clone();
@@ -161,40 +357,54 @@ fn generate_build_workspace(env: &_cargo_task_util::CTEnv) {
             ct_check_fatal!(std::fs::create_dir_all(&task_dir));
             let mut cargo_toml = task_dir.clone();
             cargo_toml.push("Cargo.toml");
-            let deps = if let Some(deps) = &task_meta.cargo_deps {
-                deps
-            } else {
-                ""
-            };
+            // script path deps are relative to the .cargo-task directory
+            let content = map_path_deps(&script_cargo_toml(task_meta), |p| {
+                absolute_path_dep(&level.cargo_task_path, p)
+            });
             ct_check_fatal!(std::fs::write(
                 &cargo_toml,
-                format!(
-                    r#"[package]
-name = "{}"
-version = "0.0.1"
-edition = "2018"
-
-[dependencies]
-cargo_task_util = "*"
-{}
-"#,
-                    task, deps,
-                )
+                add_project_crates(task_meta, &mut project_crates, content),
             ));
+            if let Some(build_rs_src) = &task_meta.build_rs {
+                let mut build_rs = task_dir.clone();
+                build_rs.push("build.rs");
+                ct_check_fatal!(std::fs::write(
+                    &build_rs,
+                    format!("{}\n", build_rs_src)
+                ));
+            }
             let mut src_dir = task_dir.clone();
             src_dir.push("src");
             ct_check_fatal!(std::fs::create_dir_all(&src_dir));
+            for (mod_name, mod_path) in script_mod_files(&level.cargo_task_path)
+            {
+                let mut mod_file = src_dir.clone();
+                mod_file.push(mod_name);
+                ct_check_fatal!(std::fs::copy(&mod_path, &mod_file));
+            }
             let mut main_file = src_dir;
             main_file.push("main.rs");
             ct_check_fatal!(std::fs::copy(&task_meta.path, &main_file));
         } else {
             copy_dir(&task_meta.path, &task_dir);
+
+            // relative path deps would otherwise resolve from the copy
+            let mut cargo_toml = task_dir.clone();
+            cargo_toml.push("Cargo.toml");
+            if let Ok(content) = std::fs::read_to_string(&cargo_toml) {
+                let content = map_path_deps(&content, |p| {
+                    absolute_path_dep(&task_meta.path, p)
+                });
+                ct_check_fatal!(std::fs::write(
+                    &cargo_toml,
+                    add_project_crates(task_meta, &mut project_crates, content),
+                ));
+            }
         }
     }
 
     // also add our cargo_task_util dep crate to the workspace
-    let ctu = "cargo_task_util".to_string();
-    all_tasks.push(&ctu);
+    all_tasks.push("cargo_task_util");
 
     ws.push("Cargo.toml");
     ct_check_fatal!(std::fs::write(
@@ -211,208 +421,1991 @@ cargo_task_util = {{ path = "cargo_task_util" }}
     ));
 }
 
-/// recursively copy a whole directory
-fn copy_dir<S: AsRef<Path>, D: AsRef<Path>>(src: S, dest: D) {
-    ct_check_fatal!(std::fs::create_dir_all(&dest));
-    for item in ct_check_fatal!(std::fs::read_dir(src)).flatten() {
-        let meta = ct_check_fatal!(item.metadata());
-        let mut dest = dest.as_ref().to_owned();
-        dest.push(item.file_name());
-        if meta.is_dir() {
-            copy_dir(item.path(), &dest);
-        } else if meta.is_file() {
-            ct_check_fatal!(std::fs::copy(item.path(), &dest));
+/// list shared `*.ct-mod.rs` modules available to script-style tasks
+/// returns (module file name, e.g. `helpers.rs`, source path)
+/// a `main.ct-mod.rs` is fatal, it would replace the task itself
+pub(crate) fn script_mod_files(
+    cargo_task_path: &Path,
+) -> Vec<(String, PathBuf)> {
+    let mut out = Vec::new();
+    for item in ct_check_fatal!(std::fs::read_dir(cargo_task_path)).flatten() {
+        let file_name = item.file_name().to_string_lossy().to_string();
+        if file_name.starts_with('.') || !file_name.ends_with(".ct-mod.rs") {
+            continue;
+        }
+        if !ct_check_fatal!(item.file_type()).is_file() {
+            continue;
         }
+        let mod_name = format!("{}.rs", &file_name[..file_name.len() - 10]);
+        if mod_name == "main.rs" {
+            ct_fatal!(
+                "{:?} would overwrite the script task main.rs, rename it",
+                item.path(),
+            );
+        }
+        out.push((mod_name, item.path()));
     }
+    out.sort();
+    out
 }
 
-/// run a specific task
-fn run_task(
-    env: &_cargo_task_util::CTEnv,
-    task_name: &str,
-    did_build_workspace: &mut bool,
-) {
-    if !env.tasks.contains_key(task_name) {
-        ct_fatal!("invalid task name '{}'", task_name);
-    }
+/// generate the Cargo.toml content for a script-style task
+pub(crate) fn script_cargo_toml(
+    task_meta: &_cargo_task_util::CTTaskMeta,
+) -> String {
+    let deps = if let Some(deps) = &task_meta.cargo_deps {
+        deps
+    } else {
+        ""
+    };
+    let cargo_toml = format!(
+        r#"[package]
+name = "{}"
+version = "0.0.1"
+edition = "{}"
 
-    let task_meta = env.tasks.get(task_name).unwrap();
-    if let Some(min_version) = &task_meta.min_version {
-        if parse_semver(crate::CARGO_TASK_VER) < parse_semver(min_version) {
-            ct_fatal!(
-                "cargo-task {} < required min version {}",
-                crate::CARGO_TASK_VER,
-                min_version,
-            );
-        }
+[dependencies]
+cargo_task_util = "*"
+{}
+"#,
+        task_meta.base_name(),
+        task_meta.edition,
+        deps,
+    );
+    match &task_meta.cargo_toml {
+        Some(fragment) => match merge_cargo_toml(&cargo_toml, fragment) {
+            Ok(cargo_toml) => cargo_toml,
+            Err(e) => ct_fatal!(
+                "invalid @ct-cargo-toml@ in task '{}': {}",
+                task_meta.name,
+                e,
+            ),
+        },
+        None => cargo_toml,
     }
+}
 
-    let task = task_build(env, task_name, did_build_workspace);
+/// a Cargo.toml line, as far as merging and rewriting manifests needs
+enum TomlLine {
+    /// a `[table]` or `[[array]]` header, without whitespace or comment
+    Header(String),
+    /// the first line of a `key = value` pair, the key without whitespace
+    Key(String),
+    /// a multi-line value continuation, a comment or an empty line
+    Other,
+}
 
-    ct_info!("run task: '{}'", task_name);
-    std::env::set_var("CT_CUR_TASK", task_name);
+/// classify the lines of a Cargo.toml, tracking multi-line arrays,
+/// inline tables and strings, so their content is never taken for
+/// a header or a key (this is not a full toml parser)
+fn toml_lines(src: &str) -> Vec<(&str, TomlLine)> {
+    let mut out = Vec::new();
+    // open `[` / `{` of the current value
+    let mut depth = 0usize;
+    let mut in_ml_string: Option<&str> = None;
 
-    let mut cmd = std::process::Command::new(task);
-    cmd.current_dir(&env.work_dir);
-    for arg in env.arg_list.iter() {
-        cmd.arg(arg);
-    }
-    cmd.stdin(std::process::Stdio::piped());
-    let res: Result<(), String> = (move || {
-        let mut child = cmd.spawn().map_err(|e| format!("{:?}", e))?;
+    for line in src.lines() {
+        let trimmed = line.trim();
+        let kind = if in_ml_string.is_some()
+            || depth > 0
+            || trimmed.is_empty()
+            || trimmed.starts_with('#')
+        {
+            TomlLine::Other
+        } else if trimmed.starts_with('[') {
+            let code = &trimmed[..toml_comment_start(trimmed)];
+            out.push((
+                line,
+                TomlLine::Header(code.split_whitespace().collect()),
+            ));
+            continue;
+        } else {
+            let key = trimmed.split('=').next().unwrap_or_default();
+            TomlLine::Key(key.split_whitespace().collect())
+        };
 
-        // drop stdin to ensure child exit
-        drop(child.stdin.take().unwrap());
+        // track the brackets and strings of the value
+        let mut chars = line.char_indices();
+        while let Some((idx, c)) = chars.next() {
+            let rest = &line[idx..];
+            if let Some(quote) = in_ml_string {
+                if rest.starts_with(quote) {
+                    in_ml_string = None;
+                    chars.nth(1);
+                } else if c == '\\' && quote == "\"\"\"" {
+                    chars.next();
+                }
+                continue;
+            }
+            match c {
+                '#' => break,
+                '"' | '\''
+                    if rest.starts_with("\"\"\"")
+                        || rest.starts_with("'''") =>
+                {
+                    in_ml_string = Some(&rest[..3]);
+                    chars.nth(1);
+                }
+                '"' | '\'' => {
+                    // skip a single line string
+                    while let Some((_, s)) = chars.next() {
+                        if s == c {
+                            break;
+                        }
+                        if s == '\\' && c == '"' {
+                            chars.next();
+                        }
+                    }
+                }
+                '[' | '{' => depth += 1,
+                ']' | '}' => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+        }
 
-        let res: Result<(), String> = (|| {
-            let status = child.wait().map_err(|e| format!("{:?}", e))?;
+        out.push((line, kind));
+    }
 
-            if !status.success() {
-                return Err(format!("{} exited non-zero", task_name));
+    out
+}
+
+/// the index of a trailing `#` comment in a toml line (or the line length)
+fn toml_comment_start(line: &str) -> usize {
+    let mut quote = None;
+    let mut chars = line.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match (quote, c) {
+            (None, '#') => return idx,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some('"'), '\\') => {
+                chars.next();
             }
+            (Some(q), c) if q == c => quote = None,
+            _ => (),
+        }
+    }
+    line.len()
+}
 
-            Ok(())
-        })();
+/// merge a raw Cargo.toml fragment into a base manifest
+/// keys in tables already present in the base are appended to that table,
+/// any other tables are appended to the end of the manifest
+fn merge_cargo_toml(base: &str, fragment: &str) -> Result<String, String> {
+    // (table header, normalized header, keys, lines)
+    let mut tables: Vec<(String, String, Vec<String>, Vec<String>)> =
+        Vec::new();
+    let mut is_fragment = false;
 
-        let mut p = env.cargo_task_target.clone();
-        let directive_file_name = format!("task-directive-{}.atat", child.id());
-        p.push(directive_file_name);
-
-        if let Ok(file) = std::fs::File::open(&p) {
-            let mut parser = at_at::AtAtParser::new(file);
-            while let Some(res) = parser.parse() {
-                for item in res {
-                    if let at_at::AtAtParseItem::KeyValue(k, v) = item {
-                        match k.as_str() {
-                            "ct-set-env" => {
-                                let idx = match v.find('=') {
-                                    Some(idx) => idx,
-                                    None => ct_fatal!(
-                                        "no '=' found in ct-set-env directive"
-                                    ),
-                                };
-                                let n = &v[..idx];
-                                let v = &v[idx + 1..];
-                                std::env::set_var(n, v);
-                                ct_info!("CT-SET-ENV: {}={}", n, v);
-                            }
-                            _ => ct_fatal!("unrecognized AtAt command '{}'", k),
+    for src in [base, fragment].iter() {
+        let mut cur: Option<usize> = None;
+        for (line, kind) in toml_lines(src) {
+            match kind {
+                TomlLine::Header(norm) => {
+                    cur = match tables.iter().position(|t| t.1 == norm) {
+                        // array of tables may legally appear more than once
+                        Some(idx) if !norm.starts_with("[[") => Some(idx),
+                        _ => {
+                            let header = line.trim().to_string();
+                            tables.push((header, norm, Vec::new(), Vec::new()));
+                            Some(tables.len() - 1)
                         }
+                    };
+                }
+                TomlLine::Key(key) => match cur {
+                    Some(idx) if tables[idx].2.contains(&key) => {
+                        return Err(format!(
+                            "duplicate key '{}' in {}",
+                            key, tables[idx].1,
+                        ));
+                    }
+                    Some(idx) => {
+                        tables[idx].2.push(key);
+                        tables[idx].3.push(line.to_string());
+                    }
+                    None if is_fragment => {
+                        return Err(format!(
+                            "'{}' must be within a [table] section",
+                            line.trim(),
+                        ));
+                    }
+                    None => (),
+                },
+                TomlLine::Other => {
+                    if let Some(idx) = cur {
+                        tables[idx].3.push(line.to_string());
                     }
                 }
             }
         }
+        is_fragment = true;
+    }
 
-        let _ = std::fs::remove_file(&p);
-
-        res
-    })();
-    std::env::remove_var("CT_CUR_TASK");
-
-    if let Err(e) = res {
-        ct_fatal!("{}", e);
+    let mut out = String::new();
+    for (header, _, _, lines) in tables {
+        out.push_str(&header);
+        out.push('\n');
+        for line in lines {
+            if !line.trim().is_empty() {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        out.push('\n');
     }
+    Ok(out)
 }
 
-/// build a specific task crate
-fn task_build(
-    env: &_cargo_task_util::CTEnv,
-    task_name: &str,
-    did_build_workspace: &mut bool,
-) -> PathBuf {
-    let task_meta = env.tasks.get(task_name).unwrap();
+/// rewrite all relative `path = "..."` values in the dependency
+/// (and patch) tables of a Cargo.toml with the given mapping function
+pub(crate) fn map_path_deps<F: Fn(&str) -> String>(
+    cargo_toml: &str,
+    f: F,
+) -> String {
+    let mut out = String::new();
+    let mut in_deps = false;
 
-    let target_dir = env.cargo_task_target.clone();
+    for (line, kind) in toml_lines(cargo_toml) {
+        if let TomlLine::Header(header) = kind {
+            in_deps =
+                header.contains("dependencies") || header.starts_with("[patch");
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
 
-    let mut artifact_path = target_dir.clone();
-    artifact_path.push("release");
-    artifact_path.push(task_name);
+        if !in_deps {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
 
-    if let Ok(meta) = std::fs::metadata(&artifact_path) {
-        let artifact_time = meta
-            .modified()
-            .expect("failed to get artifact modified time");
-        let dir_time = get_newest_time(&task_meta.path);
+        let mut rest = line;
+        while let Some(idx) = rest.find("path") {
+            let (before, after) = rest.split_at(idx);
+            out.push_str(before);
+            rest = &after[4..];
+
+            let is_key = before
+                .chars()
+                .last()
+                .map(|c| c.is_whitespace() || c == '{' || c == ',')
+                .unwrap_or(true);
+            let value = rest.trim_start();
+            let value = match value.strip_prefix('=') {
+                Some(value) if is_key => value.trim_start(),
+                _ => {
+                    out.push_str("path");
+                    continue;
+                }
+            };
+            let value = match value.strip_prefix('"') {
+                Some(value) => value,
+                None => {
+                    out.push_str("path");
+                    continue;
+                }
+            };
+            let end = match value.find('"') {
+                Some(end) => end,
+                None => {
+                    out.push_str("path");
+                    continue;
+                }
+            };
 
-        if artifact_time >= dir_time {
-            return artifact_path;
+            let path = &value[..end];
+            if Path::new(path).is_absolute() {
+                out.push_str(&format!("path = \"{}\"", path));
+            } else {
+                out.push_str(&format!("path = {:?}", f(path)));
+            }
+            rest = &value[end + 1..];
         }
+        out.push_str(rest);
+        out.push('\n');
     }
 
-    ct_info!("build task '{}'", task_name);
+    out
+}
 
-    if !*did_build_workspace {
-        *did_build_workspace = true;
-        generate_build_workspace(env);
+/// add any `@ct-use-project-crate@` path dependencies to a task manifest
+/// project workspace members are only enumerated if needed, and only once
+/// per workspace root (tasks of different levels may use different roots)
+fn add_project_crates(
+    task_meta: &_cargo_task_util::CTTaskMeta,
+    project_crates: &mut BTreeMap<PathBuf, Vec<workspace::WorkspaceMember>>,
+    cargo_toml: String,
+) -> String {
+    if task_meta.project_crates.is_empty() {
+        return cargo_toml;
     }
 
-    let mut crate_path = env.cargo_task_target.clone();
-    crate_path.push("ct-workspace");
-    crate_path.push(task_name);
+    let root = workspace::workspace_root(&task_meta.work_dir);
+    let members = project_crates
+        .entry(root.clone())
+        .or_insert_with(|| workspace::workspace_members(&root));
 
-    let mut cmd = env.cargo();
-    cmd.arg("build");
-    cmd.arg("--release");
+    let mut deps = "[dependencies]\n".to_string();
+    for name in task_meta.project_crates.iter() {
+        let member = match members.iter().find(|m| &m.name == name) {
+            Some(member) => member,
+            None => ct_fatal!(
+                "task '{}' project crate '{}' not found in {:?}, found: {:?}",
+                task_meta.name,
+                name,
+                root,
+                members.iter().map(|m| &m.name).collect::<Vec<_>>(),
+            ),
+        };
+        deps.push_str(&format!(
+            "{} = {{ path = {:?} }}\n",
+            name,
+            member.dir.to_string_lossy(),
+        ));
+    }
 
-    let mut manifest_path = crate_path;
-    manifest_path.push("Cargo.toml");
+    ct_check_fatal!(merge_cargo_toml(&cargo_toml, &deps))
+}
 
-    cmd.arg("--manifest-path");
-    cmd.arg(manifest_path);
+/// resolve a relative path dependency against a base directory
+fn absolute_path_dep(base: &Path, path: &str) -> String {
+    let mut out = base.to_owned();
+    out.push(path);
+    out.to_string_lossy().to_string()
+}
 
-    cmd.arg("--target-dir");
-    cmd.arg(&target_dir);
+/// recursively copy a whole directory
+fn copy_dir<S: AsRef<Path>, D: AsRef<Path>>(src: S, dest: D) {
+    ct_check_fatal!(std::fs::create_dir_all(&dest));
+    for item in ct_check_fatal!(std::fs::read_dir(src)).flatten() {
+        let meta = ct_check_fatal!(item.metadata());
+        let mut dest = dest.as_ref().to_owned();
+        dest.push(item.file_name());
+        if meta.is_dir() {
+            copy_dir(item.path(), &dest);
+        } else if meta.is_file() {
+            ct_check_fatal!(std::fs::copy(item.path(), &dest));
+        }
+    }
+}
 
-    ct_check_fatal!(env.exec(cmd));
+/// How an exported environment variable is modified.
+#[derive(Debug, Clone)]
+pub(crate) enum ExportOp {
+    /// `ct-set-env` - set the variable to a value
+    Set(String),
 
-    artifact_path
+    /// `ct-unset-env` - remove the variable
+    Unset,
+
+    /// `ct-path-prepend` - prepend a directory to a path list variable
+    PathPrepend(String),
+
+    /// `ct-path-append` - append a directory to a path list variable
+    PathAppend(String),
 }
 
-/// recursively get the newest update time for any file/dir
-fn get_newest_time<P: AsRef<Path>>(path: P) -> std::time::SystemTime {
-    let mut newest_time = std::time::SystemTime::UNIX_EPOCH;
+/// An environment variable modification exported by a task.
+#[derive(Debug, Clone)]
+pub(crate) struct Export {
+    /// the exporting task
+    pub task: String,
 
-    if let Ok(metadata) = std::fs::metadata(&path) {
-        if metadata.is_file() {
-            return metadata.modified().expect("failed to get modified time");
-        }
-    }
+    /// the variable name
+    pub name: String,
 
-    for item in std::fs::read_dir(&path)
-        .expect("failed to read directory")
-        .flatten()
-    {
-        let t = item.file_type().expect("failed to get file type");
+    /// the modification
+    pub op: ExportOp,
+}
 
-        if t.is_dir() {
-            let updated = get_newest_time(item.path());
-            if updated > newest_time {
-                newest_time = updated;
+impl Export {
+    /// this export as a task directive
+    fn directive(&self) -> String {
+        match &self.op {
+            ExportOp::Set(value) => {
+                format!("@ct-set-env@ {}={} @@\n", self.name, value)
             }
-        } else if t.is_file() {
-            let updated = item
-                .metadata()
-                .expect("failed to get metadata")
-                .modified()
-                .expect("failed to get modified time");
-            if updated > newest_time {
-                newest_time = updated;
+            ExportOp::Unset => format!("@ct-unset-env@ {} @@\n", self.name),
+            ExportOp::PathPrepend(dir) => {
+                format!("@ct-path-prepend@ {}={} @@\n", self.name, dir)
+            }
+            ExportOp::PathAppend(dir) => {
+                format!("@ct-path-append@ {}={} @@\n", self.name, dir)
             }
         }
     }
 
-    newest_time
-}
+    /// apply this export to a set of environment variables
+    fn apply(&self, vars: &mut BTreeMap<OsString, OsString>) {
+        let name = OsString::from(&self.name);
+        let (dir, prepend) = match &self.op {
+            ExportOp::Set(value) => {
+                vars.insert(name, value.into());
+                return;
+            }
+            ExportOp::Unset => {
+                vars.remove(&name);
+                return;
+            }
+            ExportOp::PathPrepend(dir) => (dir, true),
+            ExportOp::PathAppend(dir) => (dir, false),
+        };
+        let mut paths = vars
+            .get(&name)
+            .map(|p| std::env::split_paths(p).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if prepend {
+            paths.insert(0, dir.into());
+        } else {
+            paths.push(dir.into());
+        }
+        vars.insert(name, ct_check_fatal!(std::env::join_paths(paths)));
+    }
+}
+
+/// The environment task processes are executed with.
+/// The runner never modifies its own process environment,
+/// `ct-set-env` exports are tracked here and scoped per `@ct-env-scope@`.
+#[derive(Debug)]
+pub(crate) struct TaskEnv {
+    /// the runner environment (as set up by env_loader),
+    /// plus any `ct-env` config variables not already set
+    base: BTreeMap<OsString, OsString>,
+
+    /// task exports, in the order they were made
+    exports: Vec<Export>,
+
+    /// task outputs (task, key, value), in the order they were made
+    outputs: Vec<(String, String, String)>,
+
+    /// task processes run so far, for unique directive file names
+    runs: usize,
+}
+
+impl TaskEnv {
+    /// snapshot the runner environment
+    pub fn new(env: &_cargo_task_util::CTEnv) -> Self {
+        Self {
+            base: base_vars(env),
+            exports: Vec::new(),
+            outputs: Vec::new(),
+            runs: 0,
+        }
+    }
+
+    /// re-snapshot the runner environment after an env reload
+    /// exports are kept
+    pub fn reload_base(&mut self, env: &_cargo_task_util::CTEnv) {
+        self.base = base_vars(env);
+    }
+
+    /// apply the exports of bootstrap tasks that are visible to all tasks
+    /// to the runner's own environment, so they take part in the env
+    /// reload after bootstrapping (e.g. `CT_PROFILE`), and in task builds
+    pub fn apply_bootstrap_exports(&mut self, env: &_cargo_task_util::CTEnv) {
+        let (global, scoped): (Vec<_>, Vec<_>) =
+            self.exports.drain(..).partition(|e| {
+                env.tasks
+                    .get(&e.task)
+                    .map(|t| t.env_scope != "dependents")
+                    .unwrap_or(true)
+            });
+        self.exports = scoped;
+
+        let mut vars: BTreeMap<OsString, OsString> =
+            std::env::vars_os().collect();
+        for e in global {
+            e.apply(&mut vars);
+            match vars.get(&OsString::from(&e.name)) {
+                Some(value) => std::env::set_var(&e.name, value),
+                None => std::env::remove_var(&e.name),
+            }
+        }
+    }
+
+    /// the environment to build tasks with: the runner environment
+    /// with the exports visible to all of `task_names` applied
+    /// (`ct-env` config variables are not included)
+    pub fn build_vars(
+        &self,
+        env: &_cargo_task_util::CTEnv,
+        task_names: &[&str],
+    ) -> BTreeMap<OsString, OsString> {
+        let mut out: BTreeMap<OsString, OsString> =
+            std::env::vars_os().collect();
+        for e in self.exports.iter().filter(|e| {
+            task_names.iter().all(|t| export_visible(env, &e.task, t))
+        }) {
+            e.apply(&mut out);
+        }
+        out
+    }
+
+    /// the exports visible to a task, later exports win
+    pub fn visible_exports(
+        &self,
+        env: &_cargo_task_util::CTEnv,
+        task_name: &str,
+    ) -> Vec<&Export> {
+        self.exports
+            .iter()
+            .filter(|e| export_visible(env, &e.task, task_name))
+            .collect()
+    }
+
+    /// the runner environment with the exports visible to a task applied
+    fn exported_vars(
+        &self,
+        env: &_cargo_task_util::CTEnv,
+        task_name: &str,
+    ) -> BTreeMap<OsString, OsString> {
+        let mut out = self.base.clone();
+        for e in self.visible_exports(env, task_name) {
+            e.apply(&mut out);
+        }
+        out
+    }
+
+    /// the complete environment for a task process
+    pub fn task_vars(
+        &self,
+        env: &_cargo_task_util::CTEnv,
+        task_meta: &_cargo_task_util::CTTaskMeta,
+        member: Option<&workspace::WorkspaceMember>,
+    ) -> BTreeMap<OsString, OsString> {
+        let exported = self.exported_vars(env, &task_meta.name);
+        let mut out = self.base.clone();
+        for (name, value) in load_env_files(env, task_meta, |n| {
+            exported
+                .get(&OsString::from(n))
+                .map(|v| v.to_string_lossy().to_string())
+        }) {
+            out.insert(name.into(), value.into());
+        }
+        for e in self.visible_exports(env, &task_meta.name) {
+            e.apply(&mut out);
+        }
+        for (task, key, value) in self.outputs.iter() {
+            if depends_on(env, &task_meta.name, task, &mut HashSet::new()) {
+                out.insert(
+                    _cargo_task_util::output_env_name(task, key).into(),
+                    value.into(),
+                );
+            }
+        }
+        for (name, value) in task_ct_vars(env, task_meta, member) {
+            out.insert(name.into(), value);
+        }
+        out
+    }
+}
+
+/// the runner environment with the `ct-env` config variables applied
+/// these are only ever given to task processes, never set on the runner
+fn base_vars(env: &_cargo_task_util::CTEnv) -> BTreeMap<OsString, OsString> {
+    let mut out: BTreeMap<OsString, OsString> = std::env::vars_os().collect();
+    for (name, value) in config::load_config(&env.work_dir).env {
+        out.entry(name.into()).or_insert_with(|| value.into());
+    }
+    out
+}
+
+/// the cargo-task variables specific to a task process
+/// tasks run in the directory containing their own .cargo-task dir
+pub(crate) fn task_ct_vars(
+    env: &_cargo_task_util::CTEnv,
+    task_meta: &_cargo_task_util::CTTaskMeta,
+    member: Option<&workspace::WorkspaceMember>,
+) -> Vec<(&'static str, OsString)> {
+    let mut task_stack = std::env::var("CT_TASK_STACK").unwrap_or_default();
+    if !task_stack.is_empty() {
+        task_stack.push(' ');
+    }
+    task_stack.push_str(&task_meta.name);
+    let mut out = vec![
+        ("CT_CUR_TASK", task_meta.name.clone().into()),
+        ("CT_WORK_DIR", task_meta.work_dir.clone().into()),
+        ("CT_PATH", task_level(env, task_meta).cargo_task_path.into()),
+        ("CT_RUNNER_WORK_DIR", env.work_dir.clone().into()),
+        ("CT_RUNNER_EXE", runner_exe()),
+        ("CT_TASK_STACK", task_stack.into()),
+    ];
+    if let Some(member) = member {
+        out.push(("CT_MEMBER_NAME", member.name.clone().into()));
+        out.push(("CT_MEMBER_DIR", member.dir.clone().into()));
+    }
+    out
+}
+
+/// the path of this cargo-task binary, for `CTEnv::run_task`
+fn runner_exe() -> OsString {
+    match std::env::current_exe() {
+        Ok(exe) => exe.into(),
+        Err(e) => ct_fatal!("could not find the cargo-task executable: {}", e),
+    }
+}
+
+/// are variables exported by task `exporter` visible to task `receiver`
+pub(crate) fn export_visible(
+    env: &_cargo_task_util::CTEnv,
+    exporter: &str,
+    receiver: &str,
+) -> bool {
+    match env.tasks.get(exporter) {
+        Some(task_meta) if task_meta.env_scope == "dependents" => {
+            depends_on(env, receiver, exporter, &mut HashSet::new())
+        }
+        _ => true,
+    }
+}
+
+/// does `task` (transitively) depend on `dep`
+pub(crate) fn depends_on(
+    env: &_cargo_task_util::CTEnv,
+    task: &str,
+    dep: &str,
+    visited: &mut HashSet<String>,
+) -> bool {
+    if !visited.insert(task.to_string()) {
+        return false;
+    }
+    let task_meta = match env.tasks.get(task) {
+        Some(task_meta) => task_meta,
+        None => return false,
+    };
+    task_meta.task_deps.iter().any(|d| {
+        let d = resolve_task_name(env, d.to_string());
+        d == dep || depends_on(env, &d, dep, visited)
+    })
+}
+
+/// run a specific task
+/// if a workspace member is specified, the task is run in the member dir
+fn run_task(
+    env: &_cargo_task_util::CTEnv,
+    task_env: &mut TaskEnv,
+    task_name: &str,
+    did_build_workspace: &mut HashSet<PathBuf>,
+    member: Option<&workspace::WorkspaceMember>,
+) -> Result<(), String> {
+    if !env.tasks.contains_key(task_name) {
+        ct_fatal!("invalid task name '{}'", task_name);
+    }
+
+    let task_stack = std::env::var("CT_TASK_STACK").unwrap_or_default();
+    let task_stack = task_stack.split_whitespace().collect::<Vec<_>>();
+    if task_stack.contains(&task_name) {
+        ct_fatal!("task cycle: {} -> {}", task_stack.join(" -> "), task_name);
+    }
+
+    let task_meta = env.tasks.get(task_name).unwrap();
+    if let Err(e) = check_task_version(task_meta) {
+        ct_fatal!("{}", e);
+    }
+
+    let mut task_vars = task_env.task_vars(env, task_meta, member);
+    tools::check_task_tools(env, task_meta, &mut task_vars);
+
+    let task = task_build(env, task_env, task_name, did_build_workspace)?;
+
+    let mut cmd = std::process::Command::new(task);
+    cmd.current_dir(&task_meta.work_dir);
+    match member {
+        Some(member) => {
+            ct_info!("run task: '{}' for member '{}'", task_name, member.name);
+            cmd.current_dir(&member.dir);
+        }
+        None => ct_info!("run task: '{}'", task_name),
+    }
+    cmd.env_clear();
+    cmd.envs(task_vars);
+    for arg in env.arg_list.iter() {
+        cmd.arg(arg);
+    }
+
+    // the task (and anything it spawns) appends directives to this file
+    task_env.runs += 1;
+    let mut p = env.cargo_task_target.clone();
+    p.push(format!(
+        "task-directive-{}-{}.atat",
+        std::process::id(),
+        task_env.runs,
+    ));
+    ct_check_fatal!(std::fs::write(
+        &p,
+        format!(
+            "@ct-directive-version@ {} @@\n",
+            _cargo_task_util::DIRECTIVE_VERSION
+        ),
+    ));
+    cmd.env("CT_DIRECTIVE_FILE", &p);
+
+    // so the runner's own log lines are attributed to the task too
+    std::env::set_var("CT_CUR_TASK", task_name);
+
+    cmd.stdin(std::process::Stdio::piped());
+    let res: Result<(), String> = (|| {
+        let mut child = cmd.spawn().map_err(|e| format!("{:?}", e))?;
+
+        // drop stdin to ensure child exit
+        drop(child.stdin.take().unwrap());
+
+        let res: Result<(), String> = (|| {
+            let status = child.wait().map_err(|e| format!("{:?}", e))?;
+
+            if !status.success() {
+                return Err(format!("{} exited non-zero", task_name));
+            }
+
+            Ok(())
+        })();
+
+        read_directives(task_env, task_name, &p);
+
+        let _ = std::fs::remove_file(&p);
+
+        res
+    })();
+    std::env::remove_var("CT_CUR_TASK");
+
+    res
+}
+
+/// apply the directives a task process wrote to its directive file
+fn read_directives(task_env: &mut TaskEnv, task_name: &str, p: &Path) {
+    if let Ok(file) = std::fs::File::open(p) {
+        let mut parser = at_at::AtAtParser::new(file);
+        while let Some(res) = parser.parse() {
+            for item in res {
+                if let at_at::AtAtParseItem::KeyValue(k, v) = item {
+                    match k.as_str() {
+                        "ct-directive-version" => {
+                            if v != _cargo_task_util::DIRECTIVE_VERSION {
+                                ct_fatal!(
+                                    "unsupported directive version '{}'",
+                                    v
+                                );
+                            }
+                        }
+                        "ct-set-env" | "ct-path-prepend" | "ct-path-append" => {
+                            let idx = match v.find('=') {
+                                Some(idx) => idx,
+                                None => {
+                                    ct_fatal!("no '=' found in {} directive", k)
+                                }
+                            };
+                            let n = v[..idx].to_string();
+                            let v = v[idx + 1..].to_string();
+                            ct_info!("{}: {}={}", k.to_uppercase(), n, v);
+                            let op = match k.as_str() {
+                                "ct-set-env" => ExportOp::Set(v),
+                                "ct-path-prepend" => ExportOp::PathPrepend(v),
+                                _ => ExportOp::PathAppend(v),
+                            };
+                            task_env.exports.push(Export {
+                                task: task_name.to_string(),
+                                name: n,
+                                op,
+                            });
+                        }
+                        "ct-set-output" => {
+                            let idx = match v.find('=') {
+                                Some(idx) => idx,
+                                None => ct_fatal!(
+                                    "no '=' found in ct-set-output directive"
+                                ),
+                            };
+                            let key = v[..idx].to_string();
+                            let value = _cargo_task_util::unescape_output(
+                                &v[idx + 1..],
+                            );
+                            ct_info!("CT-SET-OUTPUT: {}.{}", task_name, key);
+                            task_env.outputs.push((
+                                task_name.to_string(),
+                                key,
+                                value,
+                            ));
+                        }
+                        "ct-unset-env" => {
+                            ct_info!("CT-UNSET-ENV: {}", v);
+                            task_env.exports.push(Export {
+                                task: task_name.to_string(),
+                                name: v,
+                                op: ExportOp::Unset,
+                            });
+                        }
+                        _ => ct_fatal!("unrecognized AtAt command '{}'", k),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// load the `.cargo-task/.env`, `.cargo-task/.env.<profile>` and any
+/// `@ct-env-file@` files for a task, in that order (later files win)
+/// variables already set for the task (`lookup`), including
+/// `ct-set-env` exports from previous tasks, take precedence
+pub(crate) fn load_env_files<F: Fn(&str) -> Option<String>>(
+    env: &_cargo_task_util::CTEnv,
+    task_meta: &_cargo_task_util::CTTaskMeta,
+    lookup: F,
+) -> Vec<(String, String)> {
+    let level = task_level(env, task_meta);
+    let mut files = vec![
+        (level.cargo_task_path.join(".env"), false),
+        (
+            level
+                .cargo_task_path
+                .join(format!(".env.{}", task_meta.profile)),
+            false,
+        ),
+    ];
+    for file in task_meta.env_files.iter() {
+        files.push((task_meta.work_dir.join(file), true));
+    }
+
+    let mut out: Vec<(String, String)> = Vec::new();
+    for (path, required) in files {
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) if !required => continue,
+            Err(e) => ct_fatal!(
+                "task '{}' env file {:?}: {:?}",
+                task_meta.name,
+                path,
+                e
+            ),
+        };
+        let vars = dotenv::parse(&content, |n| {
+            lookup(n).or_else(|| {
+                out.iter()
+                    .rev()
+                    .find(|(k, _)| k == n)
+                    .map(|(_, v)| v.clone())
+            })
+        });
+        let vars = match vars {
+            Ok(vars) => vars,
+            Err(e) => ct_fatal!("env file {:?}: {}", path, e),
+        };
+        for (name, value) in vars {
+            if lookup(&name).is_none() {
+                out.retain(|(k, _)| k != &name);
+                out.push((name, value));
+            }
+        }
+    }
+    out
+}
+
+/// build all the specified user-defined tasks that are out-of-date
+/// using a single cargo invocation per task level and build profile
+pub(crate) fn prebuild_tasks(
+    env: &_cargo_task_util::CTEnv,
+    task_env: &TaskEnv,
+    task_list: &[String],
+    did_build_workspace: &mut HashSet<PathBuf>,
+) {
+    // (level, profile, toolchain) -> task names
+    let mut by_profile: BTreeMap<(TaskLevel, &str, Option<&str>), Vec<&str>> =
+        BTreeMap::new();
+    let mut status: BTreeMap<&str, &str> = BTreeMap::new();
+    for task_name in task_list {
+        let task_meta = match env.tasks.get(task_name) {
+            Some(task_meta) => task_meta,
+            // system or psuedo task - nothing to build
+            None => continue,
+        };
+        if task_is_fresh(env, task_meta) {
+            status.insert(task_name, "up-to-date");
+        } else if let Err(e) = check_task_rust(env, task_meta) {
+            ct_warn!("{}", e);
+            status.insert(task_name, "FAILED");
+        } else {
+            by_profile
+                .entry((
+                    task_level(env, task_meta),
+                    &task_meta.profile,
+                    task_meta.toolchain.as_deref(),
+                ))
+                .or_default()
+                .push(task_name);
+        }
+    }
+
+    for ((level, profile, toolchain), task_names) in by_profile {
+        if did_build_workspace.insert(level.cargo_task_target.clone()) {
+            generate_build_workspace(env, &level);
+        }
+
+        ct_info!("build tasks {:?}", task_names);
+
+        let mut manifest_path = level.cargo_task_target.clone();
+        manifest_path.push("ct-workspace");
+        manifest_path.push("Cargo.toml");
+
+        let mut cmd = build_cargo(env, toolchain);
+        cmd.env_clear();
+        cmd.envs(task_env.build_vars(env, &task_names));
+        cmd.arg("build");
+        let keep_going = cargo_version_at_least(env, toolchain, "1.74");
+        if keep_going {
+            cmd.arg("--keep-going");
+        }
+        cmd.arg("--profile");
+        cmd.arg(profile);
+        cmd.arg("--manifest-path");
+        cmd.arg(manifest_path);
+        cmd.arg("--target-dir");
+        cmd.arg(&level.cargo_task_target);
+
+        let mut features = Vec::new();
+        for task_name in task_names.iter() {
+            let task_meta = env.tasks.get(*task_name).unwrap();
+            cmd.arg("-p");
+            cmd.arg(task_meta.base_name());
+            for feature in task_meta.features.iter() {
+                features.push(format!("{}/{}", task_meta.base_name(), feature));
+            }
+        }
+        if !features.is_empty() {
+            cmd.arg("--features");
+            cmd.arg(features.join(" "));
+        }
+
+        // the tasks cargo reported binaries for were built,
+        // without `--keep-going` cargo stops at the first failure
+        let mut built = Vec::new();
+        let success = exec_build(env, &level, cmd, &mut built).is_ok();
+
+        for task_name in task_names {
+            let task_meta = env.tasks.get(task_name).unwrap();
+            if success || built.iter().any(|b| b == task_meta.base_name()) {
+                status.insert(task_name, "built");
+            } else if keep_going {
+                status.insert(task_name, "FAILED");
+            } else {
+                status.insert(task_name, "not built");
+            }
+        }
+    }
+
+    let mut report = "task build status:".to_string();
+    for (task_name, task_status) in status.iter() {
+        report.push_str(&format!("\n{:>22} - {}", task_name, task_status));
+    }
+    ct_info!("{}", report);
+
+    let failed = status
+        .iter()
+        .filter(|(_, s)| **s == "FAILED" || **s == "not built")
+        .map(|(t, _)| *t)
+        .collect::<Vec<_>>();
+    if !failed.is_empty() {
+        ct_fatal!("failed to build tasks {:?}", failed);
+    }
+}
+
+/// is the cargo used to build tasks (with a `@ct-toolchain@`)
+/// at least `min_version`
+fn cargo_version_at_least(
+    env: &_cargo_task_util::CTEnv,
+    toolchain: Option<&str>,
+    min_version: &str,
+) -> bool {
+    let mut cmd = build_cargo(env, toolchain);
+    cmd.arg("--version");
+    match _cargo_task_util::CTCommand::from(cmd)
+        .capture()
+        .quiet()
+        .run()
+    {
+        // e.g. `cargo 1.74.0 (ecb9851af 2023-10-18)`
+        Ok(out) => _cargo_task_util::version_at_least(
+            out.stdout.split_whitespace().nth(1).unwrap_or_default(),
+            min_version,
+        ),
+        Err(_) => false,
+    }
+}
+
+/// the cargo command to build tasks with
+/// a `@ct-toolchain@` is selected by building through `rustup run`
+fn build_cargo(
+    env: &_cargo_task_util::CTEnv,
+    toolchain: Option<&str>,
+) -> std::process::Command {
+    match toolchain {
+        Some(toolchain) => {
+            let mut cmd = std::process::Command::new("rustup");
+            cmd.arg("run").arg(toolchain).arg("cargo");
+            cmd
+        }
+        None => env.cargo(),
+    }
+}
+
+/// rustc versions probed by [rustc_version] in this process, per toolchain
+static RUSTC_VERSIONS: std::sync::Mutex<
+    BTreeMap<Option<String>, Result<_cargo_task_util::CTVersion, String>>,
+> = std::sync::Mutex::new(BTreeMap::new());
+
+/// the rustc version of a toolchain (or the rustc cargo uses),
+/// ignoring any pre-release like `-nightly`
+fn rustc_version(
+    env: &_cargo_task_util::CTEnv,
+    toolchain: Option<&str>,
+) -> Result<_cargo_task_util::CTVersion, String> {
+    let key = toolchain.map(|t| t.to_string());
+    if let Some(version) = RUSTC_VERSIONS.lock().unwrap().get(&key) {
+        return version.clone();
+    }
+    let version = probe_rustc_version(env, toolchain);
+    RUSTC_VERSIONS.lock().unwrap().insert(key, version.clone());
+    version
+}
+
+/// the rustc cargo will use: `RUSTC`, else the rustc next to cargo,
+/// else `rustc` on the PATH
+pub(crate) fn cargo_rustc(env: &_cargo_task_util::CTEnv) -> OsString {
+    if let Some(rustc) = std::env::var_os("RUSTC") {
+        return rustc;
+    }
+    let rustc = env
+        .cargo_path
+        .with_file_name(format!("rustc{}", std::env::consts::EXE_SUFFIX));
+    if env.cargo_path.is_absolute() && rustc.is_file() {
+        rustc.into_os_string()
+    } else {
+        "rustc".into()
+    }
+}
+
+fn probe_rustc_version(
+    env: &_cargo_task_util::CTEnv,
+    toolchain: Option<&str>,
+) -> Result<_cargo_task_util::CTVersion, String> {
+    let mut cmd = match toolchain {
+        Some(toolchain) => {
+            let mut cmd = std::process::Command::new("rustup");
+            cmd.arg("run").arg(toolchain).arg("rustc");
+            cmd
+        }
+        None => std::process::Command::new(cargo_rustc(env)),
+    };
+    cmd.arg("--version");
+
+    let out = match (cmd.output(), toolchain) {
+        (Ok(out), _) if out.status.success() => out,
+        (_, Some(toolchain)) => {
+            return Err(format!(
+                "rust toolchain '{}' is not available, \
+                install it with 'rustup toolchain install {}'",
+                toolchain, toolchain,
+            ))
+        }
+        (Ok(out), None) => {
+            return Err(format!("'rustc --version' failed: {}", out.status))
+        }
+        (Err(e), None) => {
+            return Err(format!("could not run 'rustc --version': {}", e))
+        }
+    };
+
+    // e.g. "rustc 1.60.0 (7737e0b5c 2022-04-04)"
+    let out = String::from_utf8_lossy(&out.stdout);
+    let version = out.split_whitespace().nth(1).unwrap_or_default();
+    let mut version = _cargo_task_util::CTVersion::parse(version)?;
+    version.pre.clear();
+    version.build.clear();
+    Ok(version)
+}
+
+/// Check a task's `@ct-toolchain@` is installed, and that the rustc
+/// it will be built with satisfies its `@ct-rust-version@`.
+pub(crate) fn check_task_rust(
+    env: &_cargo_task_util::CTEnv,
+    task_meta: &_cargo_task_util::CTTaskMeta,
+) -> Result<(), String> {
+    if task_meta.toolchain.is_none() && task_meta.rust_version.is_none() {
+        return Ok(());
+    }
+
+    let version = rustc_version(env, task_meta.toolchain.as_deref())
+        .map_err(|e| format!("task '{}': {}", task_meta.name, e))?;
+
+    if let Some(rust_version) = &task_meta.rust_version {
+        if version < _cargo_task_util::parse_padded_version(rust_version)? {
+            return Err(format!(
+                "task '{}' requires rust >= {}, but rustc{} is {}, {}",
+                task_meta.name,
+                rust_version,
+                match &task_meta.toolchain {
+                    Some(toolchain) => format!(" ({})", toolchain),
+                    None => "".to_string(),
+                },
+                version,
+                match &task_meta.toolchain {
+                    Some(toolchain) =>
+                        format!("update it with 'rustup update {}'", toolchain),
+                    None => "update it with 'rustup update', \
+                        or select a toolchain with '@ct-toolchain@'"
+                        .to_string(),
+                },
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// the path to the built binary artifact for a task
+pub(crate) fn task_artifact_path(
+    env: &_cargo_task_util::CTEnv,
+    task_meta: &_cargo_task_util::CTTaskMeta,
+) -> PathBuf {
+    let mut artifact_path = task_level(env, task_meta).cargo_task_target;
+    artifact_path.push(profile_dir(&task_meta.profile));
+    artifact_path.push(task_meta.base_name());
+    artifact_path
+}
+
+/// returns true if the task artifact is newer than all the task sources,
+/// and the cargo_task_util crate (which changes when cargo-task upgrades)
+fn task_is_fresh(
+    env: &_cargo_task_util::CTEnv,
+    task_meta: &_cargo_task_util::CTTaskMeta,
+) -> bool {
+    let artifact_path = task_artifact_path(env, task_meta);
+
+    if let Ok(meta) = std::fs::metadata(&artifact_path) {
+        let artifact_time = meta
+            .modified()
+            .expect("failed to get artifact modified time");
+        let mut dir_time = get_newest_time(&task_meta.path);
+        let ctu_path = env.cargo_task_path.join("cargo_task_util");
+        if ctu_path.is_dir() {
+            let ctu_time = get_newest_time(&ctu_path);
+            if ctu_time > dir_time {
+                dir_time = ctu_time;
+            }
+        }
+        if task_meta.is_script {
+            let level = task_level(env, task_meta);
+            for (_, mod_path) in script_mod_files(&level.cargo_task_path) {
+                let mod_time = get_newest_time(&mod_path);
+                if mod_time > dir_time {
+                    dir_time = mod_time;
+                }
+            }
+        }
+
+        return artifact_time >= dir_time;
+    }
+
+    false
+}
+
+/// build a specific task crate, returning the path to the task binary
+fn task_build(
+    env: &_cargo_task_util::CTEnv,
+    task_env: &TaskEnv,
+    task_name: &str,
+    did_build_workspace: &mut HashSet<PathBuf>,
+) -> Result<PathBuf, String> {
+    let task_meta = env.tasks.get(task_name).unwrap();
+
+    let level = task_level(env, task_meta);
+
+    let artifact_path = task_artifact_path(env, task_meta);
+
+    if task_is_fresh(env, task_meta) {
+        return Ok(artifact_path);
+    }
+
+    ct_info!("build task '{}'", task_name);
+
+    check_task_rust(env, task_meta)?;
+
+    if did_build_workspace.insert(level.cargo_task_target.clone()) {
+        generate_build_workspace(env, &level);
+    }
+
+    let mut crate_path = level.cargo_task_target.clone();
+    crate_path.push("ct-workspace");
+    crate_path.push(task_meta.base_name());
+
+    let mut cmd = build_cargo(env, task_meta.toolchain.as_deref());
+    cmd.env_clear();
+    cmd.envs(task_env.build_vars(env, &[task_name]));
+    cmd.arg("build");
+    cmd.arg("--profile");
+    cmd.arg(&task_meta.profile);
+
+    if !task_meta.features.is_empty() {
+        cmd.arg("--features");
+        cmd.arg(task_meta.features.join(" "));
+    }
+
+    let mut manifest_path = crate_path;
+    manifest_path.push("Cargo.toml");
+
+    cmd.arg("--manifest-path");
+    cmd.arg(manifest_path);
+
+    cmd.arg("--target-dir");
+    cmd.arg(&level.cargo_task_target);
+
+    exec_build(env, &level, cmd, &mut Vec::new())
+        .map_err(|e| format!("failed to build task '{}': {}", task_name, e))?;
+
+    Ok(artifact_path)
+}
+
+/// execute a cargo build command in the cargo-task build workspace
+/// diagnostic paths pointing into the build workspace are rewritten
+/// to point back at the original task sources
+/// the names of the binaries cargo built (or found fresh) are added
+/// to `built`, even if the build failed
+fn exec_build(
+    env: &_cargo_task_util::CTEnv,
+    level: &TaskLevel,
+    mut cmd: std::process::Command,
+    built: &mut Vec<String>,
+) -> std::io::Result<()> {
+    use std::io::IsTerminal;
+
+    if env.offline {
+        cmd.arg("--offline");
+    }
+    if env.locked {
+        cmd.arg("--locked");
+    }
+    if env.frozen {
+        cmd.arg("--frozen");
+    }
+    if let Some(jobs) = &env.jobs {
+        cmd.arg("--jobs");
+        cmd.arg(jobs);
+    }
+
+    // not the whole command, the build environment is set explicitly
+    let non_zero_err = format!(
+        "{:?} {} exited non-zero",
+        cmd.get_program(),
+        cmd.get_args()
+            .map(|a| format!("{:?}", a))
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
+    let source_map = build_source_map(env, level);
+
+    // cargo only colors a terminal, but we pipe its output to rewrite paths
+    let color = std::env::var_os("CT_NO_COLOR").is_none()
+        && (std::env::var_os("CT_WITH_COLOR").is_some()
+            || std::io::stderr().is_terminal());
+    if color {
+        cmd.arg("--color=always");
+        cmd.arg("--message-format=json-diagnostic-rendered-ansi");
+    } else {
+        cmd.arg("--message-format=json");
+    }
+
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+    let mut child = cmd.spawn()?;
+    let stderr = child.stderr.take().unwrap();
+    let stderr_source_map = source_map.clone();
+    let stderr = std::thread::spawn(move || {
+        for_each_line(stderr, |line| {
+            eprintln!("{}", map_build_output(line, &stderr_source_map));
+        })
+    });
+    let stdout_res = for_each_line(child.stdout.take().unwrap(), |line| {
+        let msg = match json::Json::parse(line) {
+            Ok(msg) => msg,
+            Err(_) => {
+                println!("{}", line);
+                return;
+            }
+        };
+        match msg.get("reason").and_then(|r| r.as_str()) {
+            Some("compiler-message") => {
+                let rendered = msg
+                    .get("message")
+                    .and_then(|m| m.get("rendered"))
+                    .and_then(|r| r.as_str())
+                    .unwrap_or_default();
+                for line in rendered.lines() {
+                    eprintln!("{}", map_build_output(line, &source_map));
+                }
+            }
+            Some("compiler-artifact") => {
+                let target = msg.get("target");
+                let is_bin = target
+                    .and_then(|t| t.get("kind"))
+                    .and_then(|k| k.as_array())
+                    .unwrap_or_default()
+                    .iter()
+                    .any(|k| k.as_str() == Some("bin"));
+                let name =
+                    target.and_then(|t| t.get("name")).and_then(|n| n.as_str());
+                if let Some(name) = name.filter(|_| is_bin) {
+                    built.push(name.to_string());
+                }
+            }
+            _ => (),
+        }
+    });
+    // always reap the child, even if reading its output failed
+    let stderr_res = stderr.join().unwrap_or(Ok(()));
+    let success = child.wait()?.success();
+    stdout_res?;
+    stderr_res?;
+
+    // keep the lock file up-to-date in the .cargo-task directory
+    // only if the project keeps one, and only for the runner's own level,
+    // so running in a nested directory never rewrites an outer lock file
+    let mut lock_src = level.cargo_task_target.clone();
+    lock_src.push("ct-workspace");
+    lock_src.push("Cargo.lock");
+    let mut lock_dest = level.cargo_task_path.clone();
+    lock_dest.push("Cargo.lock");
+    if !env.locked
+        && !env.frozen
+        && level.cargo_task_path == env.cargo_task_path
+        && lock_dest.is_file()
+        && lock_src.is_file()
+    {
+        std::fs::copy(&lock_src, &lock_dest)?;
+    }
+
+    if !success {
+        if env.locked || env.frozen {
+            check_lock_file(env, level);
+        }
+        return Err(std::io::Error::other(non_zero_err));
+    }
+    Ok(())
+}
+
+/// call `f` with each line read from `reader`, without the line ending
+/// invalid utf8 is replaced, so a line never ends reading early
+fn for_each_line<R: std::io::Read, F: FnMut(&str)>(
+    reader: R,
+    mut f: F,
+) -> std::io::Result<()> {
+    use std::io::BufRead;
+
+    let mut reader = std::io::BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&line);
+        f(text.trim_end_matches(&['\r', '\n'][..]));
+    }
+}
+
+/// if any task dependencies are not reflected in the .cargo-task/Cargo.lock
+/// file, fatal with a message explaining which tasks changed the lock
+fn check_lock_file(env: &_cargo_task_util::CTEnv, level: &TaskLevel) {
+    let mut lock_path = level.cargo_task_path.clone();
+    lock_path.push("Cargo.lock");
+    let lock = std::fs::read_to_string(&lock_path).unwrap_or_default();
+    let lock_deps = parse_lock_deps(&lock);
+
+    let mut changed = Vec::new();
+    for (task, task_meta) in env.tasks.iter() {
+        if &task_level(env, task_meta) != level {
+            continue;
+        }
+        let deps = if task_meta.is_script {
+            let mut deps = task_meta
+                .cargo_deps
+                .as_deref()
+                .map(parse_dep_names)
+                .unwrap_or_default();
+            deps.push("cargo_task_util".to_string());
+            deps
+        } else {
+            let mut cargo_toml = task_meta.path.clone();
+            cargo_toml.push("Cargo.toml");
+            let cargo_toml =
+                std::fs::read_to_string(&cargo_toml).unwrap_or_default();
+            let mut deps = Vec::new();
+            let mut in_deps = false;
+            for (line, kind) in toml_lines(&cargo_toml) {
+                match kind {
+                    TomlLine::Header(header) => {
+                        in_deps = header == "[dependencies]";
+                    }
+                    TomlLine::Key(_) if in_deps => {
+                        deps.append(&mut parse_dep_names(line));
+                    }
+                    _ => (),
+                }
+            }
+            deps
+        };
+
+        match lock_deps.get(task_meta.base_name()) {
+            Some(locked) if deps.iter().all(|d| locked.contains(d)) => (),
+            _ => changed.push(task.as_str()),
+        }
+    }
+
+    if !changed.is_empty() {
+        ct_fatal!(
+            r"the dependencies of tasks {:?} have changed,
+{:?} needs to be updated.
+Check their @ct-cargo-deps@ / Cargo.toml, then re-run without --locked / --frozen",
+            changed,
+            lock_path,
+        );
+    }
+}
+
+/// package name -> dependency names from Cargo.lock content
+fn parse_lock_deps(lock: &str) -> BTreeMap<String, Vec<String>> {
+    let mut out = BTreeMap::new();
+    let mut name = String::new();
+    let mut in_deps = false;
+    for line in lock.lines() {
+        let line = line.trim();
+        if line == "[[package]]" {
+            name.clear();
+            in_deps = false;
+        } else if let Some(n) = line.strip_prefix("name = ") {
+            name = n.trim_matches('"').to_string();
+            out.entry(name.clone()).or_insert_with(Vec::new);
+        } else if line.starts_with("dependencies = [") {
+            in_deps = true;
+        } else if in_deps && line.starts_with(']') {
+            in_deps = false;
+        } else if in_deps {
+            // entries look like `"name"` or `"name 1.0.0"`
+            let dep = line.trim_matches(|c| c == '"' || c == ',');
+            let dep = dep.split_whitespace().next().unwrap_or_default();
+            if let Some(deps) = out.get_mut(&name) {
+                deps.push(dep.to_string());
+            }
+        }
+    }
+    out
+}
+
+/// package names from Cargo.toml style `name = ...` dependency lines
+fn parse_dep_names(deps: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in deps.lines() {
+        let line = line.trim();
+        let idx = match line.find('=') {
+            Some(idx) if !line.starts_with('#') => idx,
+            _ => continue,
+        };
+        // renamed dependencies are locked under their real package name
+        let name = match line.find("package = \"") {
+            Some(p) => line[p + 11..].split('"').next().unwrap_or_default(),
+            None => line[..idx].trim().trim_matches('"'),
+        };
+        out.push(name.to_string());
+    }
+    out
+}
+
+/// (build workspace relative path, original source path) pairs
+fn build_source_map(
+    env: &_cargo_task_util::CTEnv,
+    level: &TaskLevel,
+) -> Vec<(PathBuf, PathBuf)> {
+    let cur_dir = std::env::current_dir().unwrap_or_default();
+    let display = |p: &Path| match p.strip_prefix(&cur_dir) {
+        Ok(p) => p.to_owned(),
+        Err(_) => p.to_owned(),
+    };
+
+    let mod_files = script_mod_files(&level.cargo_task_path);
+    let mut out = Vec::new();
+    for task_meta in env.tasks.values() {
+        if &task_level(env, task_meta) != level {
+            continue;
+        }
+        let task = task_meta.base_name();
+        let mut src_dir = PathBuf::from(task);
+        src_dir.push("src");
+        if task_meta.is_script {
+            let mut main_file = src_dir.clone();
+            main_file.push("main.rs");
+            out.push((main_file, display(&task_meta.path)));
+            for (mod_name, mod_path) in mod_files.iter() {
+                let mut mod_file = src_dir.clone();
+                mod_file.push(mod_name);
+                out.push((mod_file, display(mod_path)));
+            }
+        } else {
+            out.push((PathBuf::from(task), display(&task_meta.path)));
+        }
+    }
+    // most specific first, script files before crate directories
+    out.sort_by_key(|(ws_path, _)| {
+        std::cmp::Reverse(ws_path.components().count())
+    });
+    out
+}
+
+/// rewrite build workspace paths in a line of cargo build output
+/// (with `--color=always` the location marker is wrapped in escape codes)
+fn map_build_output(line: &str, source_map: &[(PathBuf, PathBuf)]) -> String {
+    let trimmed = skip_escapes(line);
+    let loc = if trimmed.starts_with("--> ") || trimmed.starts_with("::: ") {
+        skip_escapes(&trimmed[4..])
+    } else {
+        return line.to_string();
+    };
+
+    // split off any trailing `:line:col`
+    let mut file = loc;
+    for _ in 0..2 {
+        match file.rfind(':') {
+            Some(idx)
+                if !file[idx + 1..].is_empty()
+                    && file[idx + 1..].chars().all(|c| c.is_ascii_digit()) =>
+            {
+                file = &file[..idx];
+            }
+            _ => break,
+        }
+    }
+    let suffix = &loc[file.len()..];
+
+    // match whole path components, so `fmt` doesn't match `fmt-check`
+    for (ws_path, src_path) in source_map.iter() {
+        if let Ok(rest) = Path::new(file).strip_prefix(ws_path) {
+            let src_path = if rest.as_os_str().is_empty() {
+                src_path.clone()
+            } else {
+                src_path.join(rest)
+            };
+            return format!(
+                "{}{}{}",
+                &line[..line.len() - loc.len()],
+                src_path.to_string_lossy(),
+                suffix,
+            );
+        }
+    }
+
+    line.to_string()
+}
+
+/// skip leading whitespace and terminal color escape codes
+fn skip_escapes(mut s: &str) -> &str {
+    loop {
+        s = s.trim_start();
+        match s.strip_prefix("\x1b[") {
+            Some(rest) => match rest.find(|c: char| c.is_ascii_alphabetic()) {
+                Some(idx) => s = &rest[idx + 1..],
+                None => return s,
+            },
+            None => return s,
+        }
+    }
+}
+
+/// the target sub-directory cargo places artifacts in for a profile
+fn profile_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        _ => profile,
+    }
+}
+
+/// recursively get the newest update time for any file/dir
+pub(crate) fn get_newest_time<P: AsRef<Path>>(
+    path: P,
+) -> std::time::SystemTime {
+    let mut newest_time = std::time::SystemTime::UNIX_EPOCH;
+
+    if let Ok(metadata) = std::fs::metadata(&path) {
+        if metadata.is_file() {
+            return metadata.modified().expect("failed to get modified time");
+        }
+    }
+
+    for item in std::fs::read_dir(&path)
+        .expect("failed to read directory")
+        .flatten()
+    {
+        let t = item.file_type().expect("failed to get file type");
+
+        if t.is_dir() {
+            let updated = get_newest_time(item.path());
+            if updated > newest_time {
+                newest_time = updated;
+            }
+        } else if t.is_file() {
+            let updated = item
+                .metadata()
+                .expect("failed to get metadata")
+                .modified()
+                .expect("failed to get modified time");
+            if updated > newest_time {
+                newest_time = updated;
+            }
+        }
+    }
+
+    newest_time
+}
+
+/// Check the running cargo-task version against a task's
+/// `@ct-min-version@` and `@ct-version-req@`.
+pub(crate) fn check_task_version(
+    task_meta: &_cargo_task_util::CTTaskMeta,
+) -> Result<(), String> {
+    let version = _cargo_task_util::CTVersion::parse(crate::CARGO_TASK_VER)?;
+    if let Some(min_version) = &task_meta.min_version {
+        if version < _cargo_task_util::CTVersion::parse(min_version)? {
+            return Err(format!(
+                "task '{}' requires cargo-task >= {}, this is {}",
+                task_meta.name, min_version, version,
+            ));
+        }
+    }
+    if let Some(version_req) = &task_meta.version_req {
+        if !_cargo_task_util::CTVersionReq::parse(version_req)?
+            .matches(&version)
+        {
+            return Err(format!(
+                "task '{}' requires cargo-task {}, this is {}",
+                task_meta.name, version_req, version,
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_build_output_matches_whole_components() {
+        let dir = test_dir("source-map");
+        let mut env = test_env(&dir);
+        std::fs::create_dir_all(&env.cargo_task_path).unwrap();
+        for (name, is_script) in [("fmt", false), ("fmt-check", true)].iter() {
+            let path = if *is_script {
+                env.cargo_task_path.join(format!("{}.ct.rs", name))
+            } else {
+                env.cargo_task_path.join(name)
+            };
+            env.tasks.insert(
+                name.to_string(),
+                test_task(&env, name, path, *is_script),
+            );
+        }
+        let level = TaskLevel {
+            cargo_task_path: env.cargo_task_path.clone(),
+            cargo_task_target: env.cargo_task_target.clone(),
+        };
+        let source_map = build_source_map(&env, &level);
+        let ct = env.cargo_task_path.to_string_lossy();
+
+        assert_eq!(
+            format!("  --> {}/fmt-check.ct.rs:5:18", ct),
+            map_build_output("  --> fmt-check/src/main.rs:5:18", &source_map),
+        );
+        assert_eq!(
+            format!("  --> {}/fmt/src/main.rs:1:2", ct),
+            map_build_output("  --> fmt/src/main.rs:1:2", &source_map),
+        );
+        assert_eq!(
+            format!(" \x1b[1m\x1b[94m--> \x1b[0m{}/fmt/src/main.rs:1:2", ct),
+            map_build_output(
+                " \x1b[1m\x1b[94m--> \x1b[0mfmt/src/main.rs:1:2",
+                &source_map
+            ),
+        );
+        assert_eq!(
+            "  --> fmtx/src/main.rs:1:2",
+            map_build_output("  --> fmtx/src/main.rs:1:2", &source_map),
+        );
+        assert_eq!("plain line", map_build_output("plain line", &source_map));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// a task defined in the `env` work dir
+    fn test_task(
+        env: &_cargo_task_util::CTEnv,
+        name: &str,
+        path: PathBuf,
+        is_script: bool,
+    ) -> _cargo_task_util::CTTaskMeta {
+        _cargo_task_util::CTTaskMeta {
+            name: name.to_string(),
+            is_script,
+            min_version: None,
+            version_req: None,
+            rust_version: None,
+            toolchain: None,
+            path,
+            work_dir: env.work_dir.clone(),
+            namespace: String::new(),
+            target_dir: env.cargo_task_target.clone(),
+            default: false,
+            bootstrap: false,
+            help: String::new(),
+            edition: "2018".to_string(),
+            profile: "release".to_string(),
+            cargo_deps: None,
+            cargo_toml: None,
+            build_rs: None,
+            project_crates: Vec::new(),
+            features: Vec::new(),
+            env_files: Vec::new(),
+            env_scope: "global".to_string(),
+            requires_tools: Vec::new(),
+            task_deps: Vec::new(),
+        }
+    }
+
+    /// a minimal environment rooted at `dir`, with no tasks
+    fn test_env(dir: &Path) -> _cargo_task_util::CTEnv {
+        _cargo_task_util::CTEnv {
+            cargo_path: PathBuf::from("cargo"),
+            cargo_task_path: dir.join(CARGO_TASK_DIR),
+            cargo_task_target: dir.join("target"),
+            work_dir: dir.to_path_buf(),
+            task_list: Vec::new(),
+            arg_list: Vec::new(),
+            directive_file: None,
+            offline: false,
+            locked: false,
+            frozen: false,
+            jobs: None,
+            install_tools: false,
+            managed_tools: false,
+            each_member: false,
+            member_name: None,
+            member_dir: None,
+            tasks: BTreeMap::new(),
+        }
+    }
+
+    /// a fresh, empty scratch directory for a test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "ct-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn merge_cargo_toml_tables() {
+        let base = "[package]\nname = \"t\"\n\n[dependencies]\na = \"1\"\n";
+        let fragment = "[dependencies] # more deps\nb = \"2\"\n\n\
+            [features]\nx = [\n    \"a\",\n]\n\
+            [package.metadata.m]\nlist = [\n[1, 2], # [n]\n]\n\
+            s = \"\"\"\n[not-a-table]\n\"\"\"\n";
+        assert_eq!(
+            "[package]\nname = \"t\"\n\n\
+            [dependencies]\na = \"1\"\nb = \"2\"\n\n\
+            [features]\nx = [\n    \"a\",\n]\n\n\
+            [package.metadata.m]\nlist = [\n[1, 2], # [n]\n]\n\
+            s = \"\"\"\n[not-a-table]\n\"\"\"\n\n",
+            merge_cargo_toml(base, fragment).unwrap(),
+        );
+        assert!(merge_cargo_toml(base, "b = \"2\"\n").is_err());
+        assert_eq!(
+            Err("duplicate key 'name' in [package]".to_string()),
+            merge_cargo_toml(base, "[ package ]\nname = \"u\"\n"),
+        );
+    }
+
+    #[test]
+    fn map_path_deps_relative_only() {
+        let cargo_toml = "[package]\n\
+            path = \"not-a-dep\"\n\
+            [dependencies] # local\n\
+            a = { path = \"../a\", version = \"1\" }\n\
+            b = { path = \"/abs/b\" }\n\
+            [dev-dependencies.c]\n\
+            path = \"c\"\n\
+            features = [\n\
+            [\"path\"],\n\
+            ]\n\
+            version = \"1\"\n\
+            [build-dependencies]\n\
+            d = { path = \"d\" }\n";
+        assert_eq!(
+            "[package]\n\
+            path = \"not-a-dep\"\n\
+            [dependencies] # local\n\
+            a = { path = \"/base/../a\", version = \"1\" }\n\
+            b = { path = \"/abs/b\" }\n\
+            [dev-dependencies.c]\n\
+            path = \"/base/c\"\n\
+            features = [\n\
+            [\"path\"],\n\
+            ]\n\
+            version = \"1\"\n\
+            [build-dependencies]\n\
+            d = { path = \"/base/d\" }\n",
+            map_path_deps(cargo_toml, |p| format!("/base/{}", p)),
+        );
+    }
+
+    #[test]
+    fn env_file_precedence() {
+        let dir = test_dir("env-files");
+        let env = test_env(&dir);
+        std::fs::create_dir_all(&env.cargo_task_path).unwrap();
+        std::fs::write(
+            env.cargo_task_path.join(".env"),
+            "A=env\nB=env\nC=env\nP=$A\n",
+        )
+        .unwrap();
+        std::fs::write(
+            env.cargo_task_path.join(".env.release"),
+            "B=profile\nC=profile\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("task.env"), "C=task\nD=${B}-task\n").unwrap();
+        let mut task_meta =
+            test_task(&env, "t", env.cargo_task_path.join("t.ct.rs"), true);
+        task_meta.env_files.push("task.env".to_string());
+
+        // `A` is already set, e.g. exported by a previous task
+        let vars = load_env_files(&env, &task_meta, |n| match n {
+            "A" => Some("set".to_string()),
+            _ => None,
+        });
+        let vars: BTreeMap<String, String> = vars.into_iter().collect();
+        let expect: BTreeMap<String, String> = [
+            ("B", "profile"),
+            ("C", "task"),
+            ("D", "profile-task"),
+            ("P", "set"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(expect, vars);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn output_env_names_are_unambiguous() {
+        assert_ne!(
+            _cargo_task_util::output_env_name("a_b", "c"),
+            _cargo_task_util::output_env_name("a", "b_c"),
+        );
+        assert_eq!(
+            "CT_OUTPUT_build_2ddocs__out_2ddir",
+            _cargo_task_util::output_env_name("build-docs", "out-dir"),
+        );
+        assert_eq!(
+            "CT_OUTPUT_nest_2fapp__a_3ab",
+            _cargo_task_util::output_env_name("nest/app", "a:b"),
+        );
+    }
+
+    #[test]
+    fn set_output_round_trips() {
+        let dir = test_dir("outputs");
+        let directive_file = dir.join("directive.atat");
+        std::fs::write(
+            &directive_file,
+            format!(
+                "@ct-directive-version@ {} @@\n",
+                _cargo_task_util::DIRECTIVE_VERSION
+            ),
+        )
+        .unwrap();
+        let mut env = test_env(&dir);
+        env.directive_file = Some(directive_file.clone());
+
+        let values = [
+            ("a_b", "c", "first\nsecond line\n"),
+            ("a", "b_c", " @@ not @a@ directive @@ \\n\r\t"),
+            ("round-trip", "out-dir", ""),
+        ];
+        for (task, key, value) in values.iter() {
+            env.set_output(key, value);
+            let mut task_env = TaskEnv {
+                base: BTreeMap::new(),
+                exports: Vec::new(),
+                outputs: Vec::new(),
+                runs: 0,
+            };
+            read_directives(&mut task_env, task, &directive_file);
+            let (_, _, read) = task_env.outputs.pop().unwrap();
+            std::env::set_var(
+                _cargo_task_util::output_env_name(task, key),
+                read,
+            );
+        }
+
+        for (task, key, value) in values.iter() {
+            assert_eq!(Some(value.to_string()), env.output(task, key));
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cargo_fills_empty_lock_file() {
+        let dir = test_dir("lock");
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"my-task\", \"cargo_task_util\"]\n",
+        )
+        .unwrap();
+        let deps = "util = { path = \"../cargo_task_util\", \
+            package = \"cargo_task_util\" }\n";
+        for (name, deps) in [("my-task", deps), ("cargo_task_util", "")].iter()
+        {
+            std::fs::create_dir_all(dir.join(name).join("src")).unwrap();
+            std::fs::write(
+                dir.join(name).join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.0.1\"\n\
+                    edition = \"2018\"\n\n[dependencies]\n{}",
+                    name, deps,
+                ),
+            )
+            .unwrap();
+            std::fs::write(dir.join(name).join("src/lib.rs"), "").unwrap();
+        }
+        // the documented way to start keeping a lock file
+        std::fs::write(dir.join("Cargo.lock"), "").unwrap();
+
+        let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let status = std::process::Command::new(cargo)
+            .args(["metadata", "--offline", "--format-version", "1"])
+            .current_dir(&dir)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let lock = std::fs::read_to_string(dir.join("Cargo.lock")).unwrap();
+        let lock_deps = parse_lock_deps(&lock);
+        assert_eq!(Some(&vec![]), lock_deps.get("cargo_task_util"));
+        assert_eq!(Some(&parse_dep_names(deps)), lock_deps.get("my-task"),);
 
-/// Parse a semver string into a (usize, usize, usize)
-fn parse_semver(s: &str) -> (usize, usize, usize) {
-    let r = s.split('.').collect::<Vec<_>>();
-    if r.len() != 3 {
-        ct_fatal!("invalid semver: {}", s);
+        let _ = std::fs::remove_dir_all(&dir);
     }
-    (
-        r[0].parse().unwrap(),
-        r[1].parse().unwrap(),
-        r[2].parse().unwrap(),
-    )
 }
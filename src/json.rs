@@ -0,0 +1,242 @@
+//! Minimal JSON Parsing, enough to read cargo's `--message-format=json`.
+
+/// A parsed JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    /// null
+    Null,
+    /// true / false
+    Bool(bool),
+    /// a number, kept as written
+    Number(String),
+    /// a string
+    String(String),
+    /// an array
+    Array(Vec<Json>),
+    /// an object, keys in the order they were written
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parse a complete JSON document.
+    pub fn parse(s: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: s.chars().peekable(),
+        };
+        let value = parser.value()?;
+        parser.ws();
+        match parser.chars.next() {
+            None => Ok(value),
+            Some(c) => Err(format!("unexpected trailing '{}'", c)),
+        }
+    }
+
+    /// Get an object member, `None` if missing or not an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => {
+                members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+
+    /// The string value, `None` if not a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The array items, `None` if not an array.
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// internal recursive descent parser state
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    /// skip whitespace
+    fn ws(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    /// consume an expected literal
+    fn expect(&mut self, lit: &str) -> Result<(), String> {
+        for c in lit.chars() {
+            if self.chars.next() != Some(c) {
+                return Err(format!("expected '{}'", lit));
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.ws();
+        match self.chars.peek() {
+            Some('n') => self.expect("null").map(|_| Json::Null),
+            Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if *c == '-' || c.is_ascii_digit() => Ok(self.number()),
+            Some(c) => Err(format!("unexpected '{}'", c)),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    fn number(&mut self) -> Json {
+        let mut out = String::new();
+        while let Some(c) = self.chars.peek() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                break;
+            }
+            out.push(*c);
+            self.chars.next();
+        }
+        Json::Number(out)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(out),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('u') => {
+                        let mut code = self.hex4()?;
+                        // utf16 surrogate pair
+                        if (0xd800..0xdc00).contains(&code) {
+                            self.expect("\\u")?;
+                            let low = self.hex4()?;
+                            code = 0x10000
+                                + ((code - 0xd800) << 10)
+                                + (low.wrapping_sub(0xdc00) & 0x3ff);
+                        }
+                        out.push(
+                            std::char::from_u32(code).unwrap_or('\u{fffd}'),
+                        );
+                    }
+                    Some(c) => out.push(c),
+                    None => break,
+                },
+                Some(c) => out.push(c),
+                None => break,
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| "invalid \\u escape".to_string())?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect("[")?;
+        let mut items = Vec::new();
+        self.ws();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.ws();
+            match self.chars.next() {
+                Some(',') => (),
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return Err("expected ',' or ']'".to_string()),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect("{")?;
+        let mut members = Vec::new();
+        self.ws();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.ws();
+            let key = self.string()?;
+            self.ws();
+            self.expect(":")?;
+            members.push((key, self.value()?));
+            self.ws();
+            match self.chars.next() {
+                Some(',') => (),
+                Some('}') => return Ok(Json::Object(members)),
+                _ => return Err("expected ',' or '}'".to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cargo_message() {
+        let msg = Json::parse(
+            r#"{"reason":"compiler-artifact","fresh":false,
+            "target":{"kind":["bin"],"name":"my-task"},
+            "profile":{"opt_level":"3"},"filenames":[],
+            "executable":"/t/release/my-task","n":-1.5e3,
+            "rendered":"a\n\"b\" é 😀"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some("compiler-artifact"),
+            msg.get("reason").unwrap().as_str()
+        );
+        let target = msg.get("target").unwrap();
+        assert_eq!(Some("my-task"), target.get("name").unwrap().as_str());
+        assert_eq!(
+            Some(&[Json::String("bin".to_string())][..]),
+            target.get("kind").unwrap().as_array(),
+        );
+        assert_eq!(Some(&Json::Bool(false)), msg.get("fresh"));
+        assert_eq!(Some(&Json::Number("-1.5e3".to_string())), msg.get("n"));
+        assert_eq!(
+            Some("a\n\"b\" \u{e9} \u{1f600}"),
+            msg.get("rendered").unwrap().as_str(),
+        );
+        assert_eq!(None, msg.get("missing"));
+
+        assert!(Json::parse("{\"a\":1").is_err());
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+}
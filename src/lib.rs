@@ -14,6 +14,9 @@
 //! cargo help task
 //! ```
 //!
+//! Requires Rust 1.74 or newer, which also applies to the tasks,
+//! since they are built with the generated `cargo_task_util` crate.
+//!
 //! ## Creating `cargo-task` automation tasks.
 //!
 //! ```shell
@@ -40,6 +43,136 @@
 //! This will also create a `my-task` cargo task. You can even specify cargo
 //! crate dependencies via AtAt directive `@ct-cargo-deps@` (see below).
 //!
+//! Compile errors in script tasks are reported against the original
+//! `.cargo-task/my-task.ct.rs` file, so editor jump-to-error just works.
+//!
+//! ### Converting between script and crate tasks.
+//!
+//! ```shell
+//! cargo task ct-eject my-task
+//! cargo task ct-inline my-task
+//! ```
+//!
+//! - `ct-eject` - turns `my-task.ct.rs` into a `my-task` crate directory,
+//!   moving any `@ct-cargo-deps@` into the generated Cargo.toml.
+//! - `ct-inline` - turns a `my-task` crate with only a `src/main.rs` and
+//!   simple `[dependencies]` back into a `my-task.ct.rs` script.
+//!
+//! ### Shared modules for script tasks.
+//!
+//! Any file in your `.cargo-task` directory named like `helpers.ct-mod.rs`
+//! will be copied next to every script task's `main.rs` as `helpers.rs`
+//! (so a `main.ct-mod.rs` is not allowed).
+//! Use it from your script tasks just like any other module:
+//!
+//! ```ignore
+//! mod helpers;
+//!
+//! fn main() {
+//!     helpers::do_something();
+//! }
+//! ```
+//!
+//! ### Building tasks up-front.
+//!
+//! By default, each task is built right before it is executed.
+//! `cargo task ct-build` builds all tasks in a single cargo invocation,
+//! letting cargo build them in parallel, and reports per-task build status.
+//! With cargo 1.74 or newer, all tasks that can be built are, even if some
+//! fail; older versions stop at the first failure, reporting the rest as
+//! not built.
+//! Set the `CT_PREBUILD` environment variable to do this automatically for
+//! the task list on every `cargo task` run.
+//!
+//! ### Offline and locked task builds.
+//!
+//! The lock file for task dependencies can be kept at
+//! `.cargo-task/Cargo.lock`, commit it to get reproducible task builds.
+//! To start keeping one, create an empty `.cargo-task/Cargo.lock`, it is
+//! then updated after every task build (unless `--locked` / `--frozen`).
+//! Outer-level task builds use, but never update, the outer lock file.
+//!
+//! - `--offline` (or `CT_OFFLINE`) - build tasks without network access.
+//! - `--locked` (or `CT_LOCKED`) - fail if `.cargo-task/Cargo.lock`
+//!   needs to be updated, reporting which tasks' dependencies changed.
+//! - `--frozen` (or `CT_FROZEN`) - equivalent to `--offline --locked`.
+//!
+//! ```shell
+//! cargo task --locked ci
+//! ```
+//!
+//! ### Task target directory.
+//!
+//! Tasks are built into `.cargo-task/target` by default. Set `CT_TARGET` to
+//! use a different directory, or set `CT_SHARED_TARGET` to build tasks into
+//! a `cargo-task` subdirectory of your project's target directory
+//! (respecting `CARGO_TARGET_DIR` and `build.target-dir` in
+//! `.cargo/config.toml`). `cargo task ct-clean` only removes the
+//! cargo-task directory, never your whole project target directory.
+//!
+//! ### Nested task directories.
+//!
+//! In a workspace, member crates can have their own `.cargo-task`
+//! directories. `cargo task` merges the tasks of every `.cargo-task`
+//! directory from the current directory up to the repository root
+//! (the directory containing `.git`).
+//!
+//! - Inner tasks override outer tasks with the same name.
+//! - Any task can be addressed as `<dir-name>:<task>`, e.g. `crate-a:test`
+//!   or `my-repo:test` for an overridden root task.
+//! - If an outer directory has the same name as an inner one, its
+//!   namespace is qualified with parent directory names, e.g. `repo/app:test`.
+//! - Outer tasks are built in the target directory they would use when
+//!   running `cargo task` in their own directory (a relative `CT_TARGET` is
+//!   resolved against that directory), so they are not rebuilt per level.
+//! - Each task runs with the directory containing its own `.cargo-task`
+//!   as the working directory and `CT_WORK_DIR`.
+//! - `@ct-task-deps@` may refer to tasks at any level. A dep on a task of
+//!   the same level always means that task, even if it is overridden.
+//!
+//! ### Running tasks in each workspace member.
+//!
+//! `cargo task --each-member <task-list>` runs the task list once per
+//! cargo workspace member (the root package plus the `[workspace] members`
+//! globs of the workspace root Cargo.toml), with the member directory as
+//! the working directory. Tasks can read `CTEnv::member_name` and
+//! `CTEnv::member_dir`. A per-member pass / fail summary is printed at the
+//! end, and the run fails if any member failed.
+//!
+//! ### Project configuration.
+//!
+//! Project-wide defaults can be set in `.cargo-task/config.atat`:
+//!
+//! ```ignore
+//! @ct-edition@ 2021 @@
+//! @ct-profile@ dev @@
+//! @ct-target@ target/cargo-task @@
+//! @ct-color@ false @@
+//! @ct-jobs@ 4 @@
+//! @ct-install-tools@ true @@
+//! @ct-default-tasks@ fmt clippy test @@
+//! @ct-env@
+//! RUST_BACKTRACE=1
+//! @@
+//! ```
+//!
+//! Or in a `[package.metadata.cargo-task]` (or
+//! `[workspace.metadata.cargo-task]`) table in the Cargo.toml next to
+//! `.cargo-task`, using the keys `edition`, `profile`, `target-dir`,
+//! `color`, `jobs`, `install-tools`, `default-tasks`, and a `.env` sub-table.
+//!
+//! - `ct-default-tasks` replaces any `@ct-default@` task directives.
+//! - `ct-color` `true` forces colored output (e.g. in CI logs),
+//!   `false` disables it.
+//! - unrecognized `config.atat` keys are ignored with a warning.
+//! - `ct-env` variables are set for every task, unless already set.
+//!   They are not set for the runner itself, or the cargo builds of tasks.
+//! - `ct-install-tools` installs missing `@ct-requires-tools@` tools,
+//!   `managed` installs them into a cargo-task managed root.
+//! - Precedence: cli flags, then environment variables (`CT_EDITION`,
+//!   `CT_PROFILE`, `CT_TARGET`, `CT_SHARED_TARGET`, `CT_NO_COLOR`,
+//!   `CT_JOBS`, `CT_INSTALL_TOOLS`), then `config.atat`, then the Cargo.toml table.
+//!
 //! ## Customizing how tasks are executed.
 //!
 //! `cargo-task` uses a metadata format called AtAt - because it uses `@` signs:
@@ -56,7 +189,7 @@
 //!
 //! Some things to know about AtAt:
 //! - protocol: `@key@ value @@`.
-//! - the first `@` for the key must be the first character on a line.
+//! - the first `@` for the key must start a line, after any spaces or tabs.
 //! - the value is terminated by a two ats, "`@@`".
 //! - the value can contain newlines or be on a single line.
 //! - you probably want it in a rust comment block : )
@@ -101,6 +234,107 @@
 //!
 //! Write them just as you would in your Cargo.toml.
 //!
+//! Relative `path = "..."` dependencies work as expected. For script tasks
+//! they are relative to the `.cargo-task` directory, for crate tasks they
+//! are relative to the crate directory, so tasks can depend on crates in
+//! your project.
+//!
+//! ### Project crate dependencies.
+//!
+//! ```ignore
+//! /*
+//! @ct-use-project-crate@ my-schema my-other-crate @@
+//! */
+//! ```
+//!
+//! A whitespace delimited list of crates from your project's cargo workspace
+//! that the task depends on. They are located via the `Cargo.toml` in the
+//! directory containing `.cargo-task`, and added as path dependencies.
+//! Works for both script and crate tasks.
+//!
+//! ### Extra script task manifest sections.
+//!
+//! ```ignore
+//! /*
+//! @ct-cargo-toml@
+//! [features]
+//! fancy = []
+//!
+//! [target.'cfg(windows)'.dependencies]
+//! winapi = "0.3"
+//! @@
+//!
+//! @ct-build-rs@
+//! fn main() {
+//!     println!("cargo:rerun-if-changed=build.rs");
+//! }
+//! @@
+//! */
+//! ```
+//!
+//! `@ct-cargo-toml@` fragments are merged into the script task's generated
+//! Cargo.toml. Keys in a table that already exists (like `[dependencies]`)
+//! are added to that table, other tables are appended.
+//! `@ct-build-rs@` is written out as the script task's `build.rs` file.
+//!
+//! ### Task cargo features.
+//!
+//! ```ignore
+//! /*
+//! @ct-features@ fancy other-feature @@
+//! */
+//! ```
+//!
+//! A whitespace delimited list of cargo features to enable when building
+//! the task. Works for both script and crate tasks.
+//!
+//! ### Script task rust edition.
+//!
+//! ```ignore
+//! /*
+//! @ct-edition@ 2021 @@
+//! */
+//! ```
+//!
+//! Script tasks are built with the `2018` edition by default. You can
+//! change the default for all script tasks with the `CT_EDITION` environment
+//! variable. Crate tasks specify their edition in their own Cargo.toml.
+//!
+//! ### Task build profile.
+//!
+//! ```ignore
+//! /*
+//! @ct-profile@ dev @@
+//! */
+//! ```
+//!
+//! Tasks are built with the cargo `release` profile by default. Setting the
+//! `CT_PROFILE` environment variable overrides the profile for all tasks,
+//! for example `CT_PROFILE=dev` to skip optimizations while iterating.
+//!
+//! ### Task environment files.
+//!
+//! ```ignore
+//! /*
+//! @ct-env-file@ .env.test @@
+//! */
+//! ```
+//!
+//! Dotenv-style files loaded into the task's environment, relative to the
+//! directory containing `.cargo-task`. `.cargo-task/.env` and
+//! `.cargo-task/.env.<profile>` (for the task build profile) are loaded
+//! automatically if they exist, before any `@ct-env-file@` files.
+//!
+//! - `NAME=value` lines, optionally prefixed with `export`.
+//! - `'single quoted'` values are literal, `"double quoted"` values support
+//!   escapes and can span lines.
+//! - `${VAR}` / `$VAR` are interpolated in unquoted and double quoted values.
+//! - Variables already set in the runner environment, including those
+//!   exported by earlier tasks via `CTEnv::set_env`, take precedence.
+//!
+//! `ct-init` gitignores `.cargo-task/.env*` (adding the entries to an
+//! existing `.cargo-task/.gitignore`), keep your secrets there.
+//!
 //! ### Task dependencies.
 //!
 //! ```ignore
@@ -127,6 +361,63 @@
 //! to upgrade if you are depending on features.
 //! Note, this directive works well when combined with `@ct-bootstrap@`
 //!
+//! For an upper bound too, use a cargo-style version requirement:
+//!
+//! ```ignore
+//! /*
+//! @ct-version-req@ >=0.0.13, <0.1 @@
+//! */
+//! ```
+//!
+//! Versions are parsed as SemVer 2.0, including pre-releases
+//! (`0.1.0-beta.1`). The parser is available to tasks as `CTVersion`
+//! and `CTVersionReq` in `cargo_task_util`.
+//!
+//! ### Rust toolchain requirements.
+//!
+//! ```ignore
+//! /*
+//! @ct-rust-version@ 1.80 @@
+//! @ct-toolchain@ nightly @@
+//! */
+//! ```
+//!
+//! `@ct-rust-version@` checks `rustc --version` before the task is built
+//! (the rustc of the task's toolchain, else `RUSTC` or the rustc next to
+//! `CARGO`), failing with a clear error instead of confusing compile errors
+//! on older toolchains. `@ct-toolchain@` builds the task with that rustup toolchain
+//! (through `rustup run <toolchain> cargo`), which must be installed.
+//!
+//! ### Required tools.
+//!
+//! ```ignore
+//! /*
+//! @ct-requires-tools@ cargo-readme>=3 clippy rustfmt @@
+//! */
+//! ```
+//!
+//! Before the task runs, each tool is looked for as a cargo subcommand
+//! (`cargo-readme` as `cargo readme`, `rustfmt` as `cargo fmt`), then on
+//! PATH, and the version reported by `--version` is checked against any
+//! cargo-style version requirement, e.g. `>=3` or `>=3,<4` (without spaces).
+//! Missing tools are a fatal
+//! error listing all of them, unless `ct-install-tools` is configured,
+//! in which case `clippy` and `rustfmt` are added as rustup components,
+//! and anything else is installed with `cargo install`.
+//! With `@ct-install-tools@ managed @@`, tools are installed with
+//! `cargo install --locked` into `<cargo-task target>/tools` instead,
+//! which is put on the PATH of tasks requiring tools.
+//!
+//! ### Checking your setup.
+//!
+//! `cargo task ct-doctor` prints a pass / warn / fail report on the
+//! cargo and rustc versions, whether the generated `cargo_task_util` crate
+//! is stale, the `.cargo-task/.gitignore` file, write access to the
+//! cargo-task target dir, task version and toolchain requirements,
+//! leftover directive files from interrupted runs, and every task's
+//! `@ct-requires-tools@`.
+//! It exits non-zero if anything fails.
+//!
 //! ## The magic `cargo_task_util` dependency.
 //!
 //! - [cargo_task_util on docs.rs](https://docs.rs/cargo-task/latest/cargo_task/_cargo_task_util/index.html)
@@ -165,6 +456,49 @@
 //! }
 //! ```
 //!
+//! ### Running commands from tasks.
+//!
+//! `cargo_task_util` includes a `CTCommand` builder for running commands.
+//! The command line is echoed before running, and a non-zero exit is an
+//! error that includes the exit status, and with `capture()` or `tee()`,
+//! the tail of stderr:
+//!
+//! ```ignore
+//! let env = ct_env();
+//! let out = env
+//!     .command("git")
+//!     .args(&["describe", "--tags"])
+//!     .current_dir("crates/alpha")
+//!     .env("GIT_PAGER", "")
+//!     .timeout(std::time::Duration::from_secs(30))
+//!     .capture()
+//!     .run()?;
+//! ct_info!("version: {}", out.stdout.trim());
+//!
+//! // print to the console and also append to a log file,
+//! // a failure includes the tail of stderr
+//! env.command("cargo").arg("test").tee("target/test.log").run()?;
+//! ```
+//!
+//! ### Installing cargo tools from tasks.
+//!
+//! Tasks that need a cargo subcommand can make sure it is installed.
+//! `clippy` and `fmt` are added as rustup components, other tools are
+//! installed with `cargo install cargo-<name>`:
+//!
+//! ```ignore
+//! let env = ct_env();
+//! env.ensure_cargo_tool("clippy")?;
+//!
+//! // require a minimum version, and install with `--locked` into
+//! // a cargo-task managed root, rather than the user's cargo home
+//! env.ensure_tool(CTTool::new("readme").min_version("3").managed())?;
+//! ```
+//!
+//! Found tools are cached in memory for the rest of the task process only.
+//! For `managed()` tools, the managed root is prepended to `PATH`, and like
+//! `prepend_path`, that is exported to the task runner and later tasks.
+//!
 //! ### Configuring tasks and the `cargo_task_util` crate for direct execution.
 //!
 //! So, you want to run your cargo tasks directly? The `cargo_task_util` crate
@@ -223,13 +557,90 @@
 //!
 //!     // set a variable that will be available in other tasks.
 //!     env.set_env("MY_VARIABLE", "MY_VALUE");
+//!
+//!     // remove a variable for other tasks.
+//!     env.unset_env("OTHER_VARIABLE");
+//!
+//!     // make a freshly installed tool available to other tasks.
+//!     env.prepend_path("/path/to/tool/bin");
+//! }
+//! ```
+//!
+//! These are passed to the runner as `@ct-set-env@ NAME=VALUE @@`,
+//! `@ct-unset-env@ NAME @@`, and `@ct-path-prepend@` / `@ct-path-append@`
+//! `NAME=DIR` directives. Path directives join with the platform path
+//! separator instead of replacing the existing value.
+//!
+//! Directives are appended to the file named by the `CT_DIRECTIVE_FILE`
+//! environment variable, which the runner creates for each task run
+//! (starting with a `@ct-directive-version@` header). This works through
+//! wrapper scripts, and from any helper processes the task spawns.
+//!
+//! Exported variables are passed to the processes of later tasks, and to
+//! the cargo builds of those tasks (the `ct-env` config is not). Exports of
+//! `@ct-bootstrap@` tasks (unless scoped to dependents) are also applied to
+//! the runner itself before it reloads its environment, so they can change
+//! settings like `CT_PROFILE`.
+//! By default exports are visible to all later tasks. To only export to tasks
+//! that (transitively) depend on the exporting task:
+//!
+//! ```ignore
+//! /*
+//! @ct-env-scope@ dependents @@
+//! */
+//! ```
+//!
+//! `cargo task ct-plan [task...]` prints the task order along with the
+//! variables each task will receive and whose exports it will see.
+//!
+//! ## Passing outputs to dependent tasks.
+//!
+//! Tasks can also pass values, like an artifact path, a computed version,
+//! or a list of generated files, to the tasks that depend on them:
+//!
+//! ```ignore
+//! // in the `build-docs` task
+//! ct_env().set_output("out-dir", "target/doc");
+//!
+//! // in a task with `@ct-task-deps@ build-docs @@`
+//! let out_dir = ct_env().output("build-docs", "out-dir");
+//! ```
+//!
+//! Outputs are namespaced by the producing task, can span multiple lines,
+//! and are available to all tasks that (transitively) depend on it.
+//! They are passed in `CT_OUTPUT_<task>__<key>` variables, with characters
+//! other than ascii letters and digits hex escaped as `_xx`.
+//! `ct-plan` lists which tasks each task receives outputs from.
+//!
+//! ## Running other tasks from a task.
+//!
+//! A task can invoke other tasks (with their task dependencies) on demand,
+//! for example only when some condition is met:
+//!
+//! ```ignore
+//! if needs_release {
+//!     ct_env().run_task("package", &["--release"])?;
 //! }
 //! ```
+//!
+//! This re-enters the same cargo-task binary that is running the calling
+//! task (exported to tasks as `CT_RUNNER_EXE`) with the same environment,
+//! so task builds are cached and the invoked tasks run in the project
+//! directory as usual. Environment variables exported by the invoked tasks
+//! are applied to the calling task, and exported on from it as well.
+//! Invoking a task that is already running (a task cycle) is an error.
 
 pub mod _cargo_task_util;
+#[cfg(test)]
+mod _cargo_task_util_tests;
 pub mod at_at;
+mod config;
+mod dotenv;
 mod env_loader;
+mod json;
 mod task;
+mod tools;
+mod workspace;
 
 #[cfg(windows)]
 include!(concat!(env!("OUT_DIR"), "\\ver.rs"));
@@ -10,6 +10,16 @@ mod ct_meta;
 pub use ct_meta::*;
 mod ct_clean;
 pub use ct_clean::*;
+mod ct_build;
+pub use ct_build::*;
+mod ct_eject;
+pub use ct_eject::*;
+mod ct_inline;
+pub use ct_inline::*;
+mod ct_plan;
+pub use ct_plan::*;
+mod ct_doctor;
+pub use ct_doctor::*;
 
 /// check to see if we should execute a pre-env-load task
 /// if we should - do it and exit
@@ -42,6 +52,24 @@ pub fn check_pre_env_task() {
     }
 }
 
+/// check to see if we should execute a task that takes the rest
+/// of the task list as arguments - if we should, do it and exit
+pub fn check_arg_task(env: &_cargo_task_util::CTEnv) {
+    let (task_name, args) = match env.task_list.split_first() {
+        Some(r) => r,
+        None => return,
+    };
+
+    match task_name.as_str() {
+        "ct-eject" => ct_eject(env, args),
+        "ct-inline" => ct_inline(env, args),
+        "ct-plan" => ct_plan(env, args),
+        _ => return,
+    }
+
+    std::process::exit(0);
+}
+
 /// if the task name is a system-defined task - run it and return true
 /// if not - return false - exec will attempt to run a user-defined task.
 pub fn check_system_task(
@@ -57,6 +85,14 @@ pub fn check_system_task(
             ct_clean(env);
             true
         }
+        "ct-build" => {
+            ct_build(env);
+            true
+        }
+        "ct-doctor" => {
+            ct_doctor(env);
+            true
+        }
         _ => false,
     }
 }
@@ -0,0 +1,12 @@
+use crate::*;
+
+/// Build all defined tasks up-front, reporting per-task build status.
+pub fn ct_build(env: &_cargo_task_util::CTEnv) {
+    // run ct-init to ensure our cargo_task_util crate is up-to-date
+    task::ct_init();
+
+    let task_list = env.tasks.keys().cloned().collect::<Vec<_>>();
+    let mut did_build_workspace = std::collections::HashSet::new();
+    let task_env = TaskEnv::new(env);
+    prebuild_tasks(env, &task_env, &task_list, &mut did_build_workspace);
+}
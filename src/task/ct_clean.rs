@@ -1,6 +1,96 @@
 use crate::*;
 
 pub fn ct_clean(env: &_cargo_task_util::CTEnv) {
-    ct_info!("deleting {:?}", env.cargo_task_target);
-    ct_check_fatal!(std::fs::remove_dir_all(&env.cargo_task_target));
+    let project_target = workspace::project_target_dir(&env.work_dir);
+    if env.cargo_task_target != project_target {
+        ct_info!("deleting {:?}", env.cargo_task_target);
+        ct_check_fatal!(std::fs::remove_dir_all(&env.cargo_task_target));
+        return;
+    }
+
+    // CT_TARGET is the project target dir - only delete our own files
+    ct_warn!(
+        "{:?} is the project target dir, only deleting cargo-task files",
+        env.cargo_task_target,
+    );
+    let mut ws = env.cargo_task_target.clone();
+    ws.push("ct-workspace");
+    let _ = std::fs::remove_dir_all(&ws);
+    let mut outer = env.cargo_task_target.clone();
+    outer.push("ct-outer");
+    let _ = std::fs::remove_dir_all(&outer);
+    let mut crate_names = vec!["cargo_task_util".to_string()];
+    for task_meta in env.tasks.values() {
+        let artifact_path = task_artifact_path(env, task_meta);
+        ct_info!("deleting {:?}", artifact_path);
+        let _ = std::fs::remove_file(&artifact_path);
+        let _ = std::fs::remove_file(artifact_path.with_extension("d"));
+        crate_names.push(task_meta.base_name().to_string());
+    }
+
+    // the intermediate build files of the task crates, in every profile
+    for profile in std::fs::read_dir(&env.cargo_task_target)
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        for sub in &["deps", ".fingerprint", "build", "incremental"] {
+            let dir = profile.path().join(sub);
+            for item in std::fs::read_dir(&dir).into_iter().flatten().flatten()
+            {
+                let file_name = item.file_name().to_string_lossy().to_string();
+                if !crate_names.iter().any(|c| is_crate_file(c, &file_name)) {
+                    continue;
+                }
+                let path = item.path();
+                let _ = if path.is_dir() {
+                    std::fs::remove_dir_all(&path)
+                } else {
+                    std::fs::remove_file(&path)
+                };
+            }
+        }
+    }
+}
+
+/// is `file_name` a cargo build file of crate `crate_name`, i.e.
+/// `<crate>-<hash>` with an optional extension and `lib` prefix,
+/// cargo uses the package name, or the crate name with `_` for `-`
+fn is_crate_file(crate_name: &str, file_name: &str) -> bool {
+    let names = [crate_name.to_string(), crate_name.replace('-', "_")];
+    let files = [Some(file_name), file_name.strip_prefix("lib")];
+    for file_name in files.iter().flatten() {
+        for name in names.iter() {
+            let rest = match file_name
+                .strip_prefix(name.as_str())
+                .and_then(|r| r.strip_prefix('-'))
+            {
+                Some(rest) => rest,
+                None => continue,
+            };
+            let hash = rest.split('.').next().unwrap_or_default();
+            if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crate_files() {
+        assert!(is_crate_file("my-task", "my-task-0123456789abcdef"));
+        assert!(is_crate_file("my-task", "my_task-0123456789abcdef.d"));
+        assert!(is_crate_file(
+            "cargo_task_util",
+            "libcargo_task_util-0123456789abcdef.rlib"
+        ));
+        assert!(!is_crate_file("my-task", "my-task-core-0123456789abcdef"));
+        assert!(!is_crate_file("my-task", "my-task-0123"));
+        assert!(!is_crate_file("my-task", "other-0123456789abcdef"));
+    }
 }
@@ -0,0 +1,281 @@
+use crate::{_cargo_task_util::ToolStatus, *};
+use std::collections::BTreeMap;
+
+/// Check the cargo-task setup and print a pass / warn / fail report.
+/// - cargo and rustc versions, and the resolved `CARGO` path.
+/// - whether task binaries were built against an older `cargo_task_util`.
+/// - whether the `.cargo-task/.gitignore` file is present.
+/// - write access to the cargo-task target dir.
+/// - that every task's `@ct-min-version@` / `@ct-version-req@` is satisfied.
+/// - that every task's `@ct-toolchain@` / `@ct-rust-version@` is satisfied.
+/// - leftover directive files from interrupted runs.
+/// - that every task's `@ct-requires-tools@` are available.
+pub fn ct_doctor(env: &_cargo_task_util::CTEnv) {
+    let mut report = Report::default();
+
+    check_versions(env, &mut report);
+    check_util_crate(env, &mut report);
+    check_gitignore(env, &mut report);
+    check_target(env, &mut report);
+    check_min_versions(env, &mut report);
+    check_directive_files(env, &mut report);
+    check_tools(env, &mut report);
+
+    ct_info!(
+        "ct-doctor report:{}\n{} passed, {} warnings, {} failed",
+        report.out,
+        report.pass,
+        report.warn,
+        report.fail,
+    );
+
+    if report.fail > 0 {
+        ct_fatal!("ct-doctor found {} problem(s)", report.fail);
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    out: String,
+    pass: usize,
+    warn: usize,
+    fail: usize,
+}
+
+impl Report {
+    fn pass(&mut self, msg: String) {
+        self.pass += 1;
+        self.out.push_str(&format!("\n  pass - {}", msg));
+    }
+
+    fn warn(&mut self, msg: String) {
+        self.warn += 1;
+        self.out.push_str(&format!("\n  WARN - {}", msg));
+    }
+
+    fn fail(&mut self, msg: String) {
+        self.fail += 1;
+        self.out.push_str(&format!("\n  FAIL - {}", msg));
+    }
+}
+
+/// run `<program> --version`, returning the first line of output
+fn tool_version(program: &std::ffi::OsStr) -> Result<String, String> {
+    let out = _cargo_task_util::CTCommand::new(program)
+        .arg("--version")
+        .capture()
+        .quiet()
+        .run()
+        .map_err(|e| e.to_string())?;
+    Ok(out.stdout.lines().next().unwrap_or_default().to_string())
+}
+
+fn check_versions(env: &_cargo_task_util::CTEnv, report: &mut Report) {
+    match tool_version(env.cargo_path.as_os_str()) {
+        Ok(v) => report.pass(format!("{} (CARGO={:?})", v, env.cargo_path)),
+        Err(e) => {
+            report.fail(format!("cannot run CARGO={:?}: {}", env.cargo_path, e))
+        }
+    }
+
+    let rustc = exec::cargo_rustc(env);
+    match tool_version(&rustc) {
+        Ok(v) => report.pass(v),
+        Err(e) => report.fail(format!("cannot run {:?}: {}", rustc, e)),
+    }
+}
+
+fn check_util_crate(env: &_cargo_task_util::CTEnv, report: &mut Report) {
+    let ctu_path = env.cargo_task_path.join("cargo_task_util");
+    if !ctu_path.is_dir() {
+        report.warn(format!(
+            "{:?} not found, it will be generated on the next task run",
+            ctu_path,
+        ));
+        return;
+    }
+    let ctu_time = get_newest_time(&ctu_path);
+
+    let artifacts = env
+        .tasks
+        .values()
+        .map(|task_meta| {
+            let modified =
+                std::fs::metadata(task_artifact_path(env, task_meta))
+                    .and_then(|m| m.modified())
+                    .ok();
+            (task_meta.name.clone(), modified)
+        })
+        .collect::<Vec<_>>();
+
+    report_util_crate(report, ctu_time, &artifacts);
+}
+
+/// warn about task binaries built against an older cargo_task_util crate,
+/// tasks that have not been built yet are skipped
+fn report_util_crate(
+    report: &mut Report,
+    ctu_time: std::time::SystemTime,
+    artifacts: &[(String, Option<std::time::SystemTime>)],
+) {
+    let stale = artifacts
+        .iter()
+        .filter(|(_, modified)| matches!(modified, Some(t) if *t < ctu_time))
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+
+    if stale.is_empty() {
+        report
+            .pass("task binaries match the cargo_task_util crate".to_string());
+    } else {
+        report.warn(format!(
+            "task binaries built against an older cargo_task_util crate, \
+            they will be rebuilt on their next run: {:?}",
+            stale,
+        ));
+    }
+}
+
+fn check_gitignore(env: &_cargo_task_util::CTEnv, report: &mut Report) {
+    let gitignore = env.cargo_task_path.join(".gitignore");
+    if gitignore.is_file() {
+        report.pass(format!("{:?} present", gitignore));
+    } else {
+        report.warn(format!(
+            "{:?} not found, run 'cargo task ct-init' to create it",
+            gitignore,
+        ));
+    }
+}
+
+fn check_target(env: &_cargo_task_util::CTEnv, report: &mut Report) {
+    let probe = env
+        .cargo_task_target
+        .join(format!("ct-doctor-{}", std::process::id()));
+    let res = std::fs::create_dir_all(&env.cargo_task_target)
+        .and_then(|_| std::fs::write(&probe, b""))
+        .and_then(|_| std::fs::remove_file(&probe));
+    match res {
+        Ok(_) => report
+            .pass(format!("CT_TARGET {:?} is writable", env.cargo_task_target)),
+        Err(e) => report.fail(format!(
+            "CT_TARGET {:?} is not writable: {}",
+            env.cargo_task_target, e,
+        )),
+    }
+}
+
+fn check_min_versions(env: &_cargo_task_util::CTEnv, report: &mut Report) {
+    let mut ok = true;
+    for task_meta in env.tasks.values() {
+        if let Err(e) = check_task_version(task_meta) {
+            ok = false;
+            report.fail(e);
+        }
+        if let Err(e) = check_task_rust(env, task_meta) {
+            ok = false;
+            report.fail(e);
+        }
+    }
+    if ok {
+        report.pass("all task version requirements are satisfied".to_string());
+    }
+}
+
+fn check_directive_files(env: &_cargo_task_util::CTEnv, report: &mut Report) {
+    let mut leftover = Vec::new();
+    if let Ok(items) = std::fs::read_dir(&env.cargo_task_target) {
+        for item in items.flatten() {
+            let name = item.file_name().to_string_lossy().to_string();
+            if (name.starts_with("task-directive-")
+                || name.starts_with("task-return-"))
+                && name.ends_with(".atat")
+            {
+                leftover.push(name);
+            }
+        }
+    }
+    leftover.sort();
+
+    if leftover.is_empty() {
+        report.pass("no leftover directive files".to_string());
+    } else {
+        report.warn(format!(
+            "leftover directive files in {:?} (from interrupted runs?), \
+            safe to delete if no task is running: {:?}",
+            env.cargo_task_target, leftover,
+        ));
+    }
+}
+
+fn check_tools(env: &_cargo_task_util::CTEnv, report: &mut Report) {
+    let mut vars = std::env::vars_os().collect::<BTreeMap<_, _>>();
+    tools::add_tool_root(env, &mut vars);
+    for task_meta in env.tasks.values() {
+        for req in task_meta.requires_tools.iter() {
+            let req = match tools::parse_tool_req(req) {
+                Ok(req) => req,
+                Err(e) => {
+                    report.fail(format!("task '{}' {}", task_meta.name, e));
+                    continue;
+                }
+            };
+            let msg = match tools::probe_tool(env, &req, &vars) {
+                ToolStatus::Ok(version) => {
+                    report.pass(format!(
+                        "task '{}' tool {} found ({})",
+                        task_meta.name, req, version,
+                    ));
+                    continue;
+                }
+                ToolStatus::TooOld(version) => format!(
+                    "task '{}' tool {} too old (found {})",
+                    task_meta.name, req, version,
+                ),
+                ToolStatus::Missing => {
+                    format!("task '{}' tool {} not found", task_meta.name, req)
+                }
+            };
+            if env.install_tools {
+                report.warn(format!("{}, it will be installed", msg));
+            } else {
+                report.fail(msg);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn util_crate_report() {
+        let ctu_time = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let old = Some(ctu_time - Duration::from_secs(1));
+        let new = Some(ctu_time + Duration::from_secs(1));
+
+        let mut report = Report::default();
+        report_util_crate(
+            &mut report,
+            ctu_time,
+            &[("a".to_string(), new), ("b".to_string(), None)],
+        );
+        assert_eq!((1, 0, 0), (report.pass, report.warn, report.fail));
+
+        let mut report = Report::default();
+        report_util_crate(
+            &mut report,
+            ctu_time,
+            &[
+                ("a".to_string(), new),
+                ("b".to_string(), old),
+                ("c".to_string(), None),
+            ],
+        );
+        assert_eq!((0, 1, 0), (report.pass, report.warn, report.fail));
+        assert!(report.out.starts_with("\n  WARN - "));
+        assert!(report.out.ends_with("[\"b\"]"));
+    }
+}
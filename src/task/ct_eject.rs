@@ -0,0 +1,101 @@
+use crate::*;
+
+/// Convert script-style `*.ct.rs` tasks into crate-style task directories.
+/// - `@ct-cargo-deps@` are moved into a real Cargo.toml `[dependencies]`.
+/// - `@ct-edition@` is moved into the Cargo.toml `[package]`.
+/// - `@ct-cargo-toml@` is merged into the Cargo.toml.
+/// - `@ct-build-rs@` is written out to `<task>/build.rs`.
+/// - The remaining source is written to `<task>/src/main.rs`.
+/// - Shared `*.ct-mod.rs` modules the task declares with `mod <name>;`
+///   are copied in next to `main.rs`.
+pub fn ct_eject(env: &_cargo_task_util::CTEnv, task_names: &[String]) {
+    if task_names.is_empty() {
+        ct_fatal!("usage: cargo task ct-eject <task-name> [task-name...]");
+    }
+
+    for task_name in task_names {
+        let task_meta = match env.tasks.get(task_name) {
+            Some(task_meta) => task_meta,
+            None => ct_fatal!("invalid task name '{}'", task_name),
+        };
+
+        if !task_meta.is_script {
+            ct_fatal!("task '{}' is already a crate-style task", task_name);
+        }
+
+        let level = task_level(env, task_meta);
+        let mut task_dir = level.cargo_task_path.clone();
+        task_dir.push(task_meta.base_name());
+        if std::fs::metadata(&task_dir).is_ok() {
+            ct_fatal!("cannot eject '{}', {:?} exists", task_name, task_dir);
+        }
+
+        let src = ct_check_fatal!(std::fs::read_to_string(&task_meta.path));
+        let src = at_at::strip_key(&src, "ct-cargo-deps");
+        let src = at_at::strip_key(&src, "ct-edition");
+        let src = at_at::strip_key(&src, "ct-cargo-toml");
+        let src = at_at::strip_key(&src, "ct-build-rs");
+
+        let mut src_dir = task_dir.clone();
+        src_dir.push("src");
+        ct_check_fatal!(std::fs::create_dir_all(&src_dir));
+
+        let mut cargo_toml = task_dir.clone();
+        cargo_toml.push("Cargo.toml");
+        // path deps were relative to .cargo-task, now one level deeper
+        ct_check_fatal!(std::fs::write(
+            &cargo_toml,
+            map_path_deps(&script_cargo_toml(task_meta), |p| {
+                format!("../{}", p)
+            }),
+        ));
+
+        // only the shared modules the task uses, see ct_inline
+        let mods = declared_mods(&src);
+        for (mod_name, mod_path) in script_mod_files(&level.cargo_task_path) {
+            if !mods.iter().any(|m| format!("{}.rs", m) == mod_name) {
+                continue;
+            }
+            let mut mod_file = src_dir.clone();
+            mod_file.push(mod_name);
+            ct_check_fatal!(std::fs::copy(&mod_path, &mod_file));
+        }
+
+        if let Some(build_rs_src) = &task_meta.build_rs {
+            let mut build_rs = task_dir.clone();
+            build_rs.push("build.rs");
+            ct_check_fatal!(std::fs::write(
+                &build_rs,
+                format!("{}\n", build_rs_src)
+            ));
+        }
+
+        let mut main_file = src_dir;
+        main_file.push("main.rs");
+        ct_check_fatal!(std::fs::write(&main_file, src));
+
+        ct_check_fatal!(std::fs::remove_file(&task_meta.path));
+
+        ct_info!("ejected {:?} into {:?}", task_meta.path, task_dir);
+    }
+}
+
+/// the names of the out-of-line modules (`mod name;`) declared in a source
+fn declared_mods(src: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in src.lines() {
+        let mut line = line.trim();
+        for vis in &["pub(crate) ", "pub "] {
+            if let Some(rest) = line.strip_prefix(vis) {
+                line = rest.trim_start();
+            }
+        }
+        if let Some(name) = line
+            .strip_prefix("mod ")
+            .and_then(|rest| rest.strip_suffix(';'))
+        {
+            out.push(name.trim().to_string());
+        }
+    }
+    out
+}
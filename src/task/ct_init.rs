@@ -2,7 +2,7 @@ use crate::*;
 
 /// This idempotent task runs before any valid task is executed or on ct_init.
 /// - Ensure the .cargo-task directory exists.
-/// - Ensure the .cargo-task/.gitignore file is initialized.
+/// - Ensure the .cargo-task/.gitignore file is initialized (and complete).
 /// - Ensure the .cargo-task/cargo_task_util dep crate exists.
 pub fn ct_init() {
     check_cargo_task_dir();
@@ -21,12 +21,49 @@ fn check_cargo_task_dir() {
 }
 
 fn check_gitignore() {
-    if let Ok(meta) = std::fs::metadata(CT_DIR_GIT_IGNORE) {
-        if meta.is_file() {
+    let existing = match std::fs::read_to_string(CT_DIR_GIT_IGNORE) {
+        Ok(existing) => existing,
+        Err(_) => {
+            ct_check_fatal!(std::fs::write(
+                CT_DIR_GIT_IGNORE,
+                CT_DIR_GIT_IGNORE_SRC
+            ));
             return;
         }
+    };
+
+    let missing = gitignore_additions(&existing, GIT_IGNORE_ADDED);
+    if missing.is_empty() {
+        return;
+    }
+    ct_info!(
+        "adding {} to {}",
+        missing.lines().collect::<Vec<_>>().join(", "),
+        CT_DIR_GIT_IGNORE,
+    );
+    let mut content = existing;
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&missing);
+    ct_check_fatal!(std::fs::write(CT_DIR_GIT_IGNORE, content));
+}
+
+/// .gitignore entries added since older versions wrote the file, appended
+/// to existing files - other lines may have been deleted on purpose,
+/// to commit the `cargo_task_util` crate, but secrets must stay ignored
+const GIT_IGNORE_ADDED: &[&str] = &["/.env", "/.env.*"];
+
+/// the `entries` not already in the `existing` .gitignore
+fn gitignore_additions(existing: &str, entries: &[&str]) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        if !existing.lines().any(|l| l.trim() == *entry) {
+            out.push_str(entry);
+            out.push('\n');
+        }
     }
-    ct_check_fatal!(std::fs::write(CT_DIR_GIT_IGNORE, CT_DIR_GIT_IGNORE_SRC));
+    out
 }
 
 fn check_cargo_task_util_crate() {
@@ -52,14 +89,14 @@ fn check_util_cargo_toml() {
     cargo_toml.push("cargo_task_util");
     cargo_toml.push("Cargo.toml");
 
-    ct_check_fatal!(std::fs::write(
+    write_if_changed(
         &cargo_toml,
         r#"[package]
 name = "cargo_task_util"
 version = "0.0.1"
 edition = "2018"
-"#
-    ));
+"#,
+    );
 }
 
 fn check_util_lib_rs() {
@@ -76,7 +113,7 @@ pub mod _cargo_task_util;
 pub use _cargo_task_util::*;
 "#;
 
-    ct_check_fatal!(std::fs::write(&lib_rs, CONTENT));
+    write_if_changed(&lib_rs, CONTENT);
 }
 
 fn check_util_ctu_rs() {
@@ -88,5 +125,34 @@ fn check_util_ctu_rs() {
 
     ctu_rs.push("_cargo_task_util.rs");
 
-    ct_check_fatal!(std::fs::write(&ctu_rs, CARGO_TASK_UTIL_SRC));
+    write_if_changed(&ctu_rs, CARGO_TASK_UTIL_SRC);
+}
+
+/// only write a file if its content differs, so its modified time
+/// tells task builds when the util crate actually changed
+fn write_if_changed<C: AsRef<[u8]>>(path: &std::path::Path, content: C) {
+    let content = content.as_ref();
+    if std::fs::read(path).ok().as_deref() == Some(content) {
+        return;
+    }
+    ct_check_fatal!(std::fs::write(path, content));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gitignore_entries() {
+        let src = String::from_utf8_lossy(CT_DIR_GIT_IGNORE_SRC);
+        assert_eq!("", gitignore_additions(&src, GIT_IGNORE_ADDED));
+        assert_eq!(
+            "/.env\n/.env.*\n",
+            gitignore_additions("/target\n/mine\n", GIT_IGNORE_ADDED),
+        );
+        assert_eq!(
+            "/.env.*\n",
+            gitignore_additions("/target\n/.env \n", GIT_IGNORE_ADDED),
+        );
+    }
 }
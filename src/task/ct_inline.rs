@@ -0,0 +1,180 @@
+use crate::*;
+
+/// Convert simple crate-style tasks back into `*.ct.rs` script tasks.
+/// The crate must consist of only a `src/main.rs` file (plus unchanged
+/// copies of shared `*.ct-mod.rs` modules, as left by ct-eject), and a
+/// Cargo.toml with only a `[package]` and `[dependencies]` section.
+pub fn ct_inline(env: &_cargo_task_util::CTEnv, task_names: &[String]) {
+    if task_names.is_empty() {
+        ct_fatal!("usage: cargo task ct-inline <task-name> [task-name...]");
+    }
+
+    for task_name in task_names {
+        let task_meta = match env.tasks.get(task_name) {
+            Some(task_meta) => task_meta,
+            None => ct_fatal!("invalid task name '{}'", task_name),
+        };
+
+        if task_meta.is_script {
+            ct_fatal!("task '{}' is already a script-style task", task_name);
+        }
+
+        let cargo_task_path = task_level(env, task_meta).cargo_task_path;
+        let mut script = cargo_task_path.clone();
+        script.push(format!("{}.ct.rs", task_meta.base_name()));
+        if std::fs::metadata(&script).is_ok() {
+            ct_fatal!("cannot inline '{}', {:?} exists", task_name, script);
+        }
+
+        check_single_file(task_name, &task_meta.path, &cargo_task_path);
+
+        let mut cargo_toml = task_meta.path.clone();
+        cargo_toml.push("Cargo.toml");
+        let cargo_toml = ct_check_fatal!(std::fs::read_to_string(&cargo_toml));
+
+        // path deps will now be relative to the .cargo-task directory
+        let inner_path = std::cell::Cell::new(false);
+        let cargo_toml =
+            map_path_deps(&cargo_toml, |p| match p.strip_prefix("../") {
+                Some(p) => p.to_string(),
+                None => {
+                    inner_path.set(true);
+                    p.to_string()
+                }
+            });
+        if inner_path.get() {
+            ct_fatal!(
+                "cannot inline '{}', path dependency within the crate",
+                task_name,
+            );
+        }
+        let (edition, deps) = match parse_simple_manifest(&cargo_toml) {
+            Ok(r) => r,
+            Err(e) => ct_fatal!("cannot inline '{}', {}", task_name, e),
+        };
+
+        let mut header = String::new();
+        if std::env::var("CT_EDITION").ok().as_ref() != Some(&edition) {
+            header.push_str(&format!("@ct-edition@ {} @@\n", edition));
+        }
+        if !deps.is_empty() {
+            header.push_str(&format!(
+                "@ct-cargo-deps@\n{}\n@@\n",
+                deps.join("\n")
+            ));
+        }
+
+        let mut main_file = task_meta.path.clone();
+        main_file.push("src");
+        main_file.push("main.rs");
+        let mut src = ct_check_fatal!(std::fs::read_to_string(&main_file));
+
+        if !header.is_empty() {
+            src = format!("/*\n{}*/\n\n{}", header, src);
+        }
+
+        ct_check_fatal!(std::fs::write(&script, src));
+        ct_check_fatal!(std::fs::remove_dir_all(&task_meta.path));
+
+        ct_info!("inlined {:?} into {:?}", task_meta.path, script);
+    }
+}
+
+/// make sure the crate only contains a Cargo.toml and src/main.rs,
+/// other source files must be identical to a shared `*.ct-mod.rs` module
+/// the script task will get again once inlined
+fn check_single_file(
+    task_name: &str,
+    path: &std::path::Path,
+    cargo_task_path: &std::path::Path,
+) {
+    let mod_files = script_mod_files(cargo_task_path);
+    for item in ct_check_fatal!(std::fs::read_dir(path)).flatten() {
+        let file_name = item.file_name().to_string_lossy().to_string();
+        match file_name.as_str() {
+            "Cargo.toml" | "Cargo.lock" | "target" => (),
+            "src" => {
+                for item in
+                    ct_check_fatal!(std::fs::read_dir(item.path())).flatten()
+                {
+                    if item.file_name() == "main.rs" {
+                        continue;
+                    }
+                    let shared = mod_files.iter().find(|(mod_name, _)| {
+                        item.file_name() == mod_name.as_str()
+                    });
+                    match shared {
+                        Some((_, mod_path))
+                            if std::fs::read(mod_path).ok()
+                                == std::fs::read(item.path()).ok() => {}
+                        Some((_, mod_path)) => ct_fatal!(
+                            "cannot inline '{}', {:?} differs from {:?}",
+                            task_name,
+                            item.path(),
+                            mod_path,
+                        ),
+                        None => ct_fatal!(
+                            "cannot inline '{}', extra source file {:?}",
+                            task_name,
+                            item.path(),
+                        ),
+                    }
+                }
+            }
+            _ => ct_fatal!(
+                "cannot inline '{}', extra file {:?}",
+                task_name,
+                item.path(),
+            ),
+        }
+    }
+}
+
+/// pull the edition and `[dependencies]` lines out of a simple task Cargo.toml
+fn parse_simple_manifest(
+    cargo_toml: &str,
+) -> Result<(String, Vec<String>), String> {
+    // cargo's default if no edition is specified
+    let mut edition = "2015".to_string();
+    let mut deps = Vec::new();
+    let mut section = "";
+
+    for line in cargo_toml.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            section = line;
+            if section != "[package]" && section != "[dependencies]" {
+                return Err(format!("unsupported Cargo.toml section {}", line));
+            }
+            continue;
+        }
+
+        let key = match line.find('=') {
+            Some(idx) => line[..idx].trim(),
+            None => {
+                return Err(format!("unsupported Cargo.toml line {}", line))
+            }
+        };
+
+        if section == "[package]" {
+            match key {
+                "name" | "version" | "authors" | "publish" => (),
+                "edition" => {
+                    edition = line[line.find('=').unwrap() + 1..]
+                        .trim()
+                        .trim_matches('"')
+                        .to_string();
+                }
+                _ => return Err(format!("unsupported [package] key {}", key)),
+            }
+        } else if key != "cargo_task_util" {
+            deps.push(line.to_string());
+        }
+    }
+
+    Ok((edition, deps))
+}
@@ -0,0 +1,58 @@
+use crate::*;
+
+/// Print the task execution order and the environment each task receives,
+/// without building or running anything.
+/// - `CT_` variables specific to the task.
+/// - variable names loaded from env files (values are not printed).
+/// - which earlier tasks' `set_env` exports are visible to the task.
+/// - which earlier tasks' `set_output` outputs are available to the task.
+pub fn ct_plan(env: &_cargo_task_util::CTEnv, task_names: &[String]) {
+    let task_list = resolve_task_list(env, task_names);
+
+    let mut out = "task plan:".to_string();
+    for (idx, task_name) in task_list.iter().enumerate() {
+        let task_meta = match env.tasks.get(task_name) {
+            Some(task_meta) => task_meta,
+            None => {
+                out.push_str(&format!("\n{:>4}. {}", idx + 1, task_name));
+                continue;
+            }
+        };
+
+        out.push_str(&format!(
+            "\n{:>4}. {} (export scope: {})",
+            idx + 1,
+            task_name,
+            task_meta.env_scope,
+        ));
+        for (name, value) in task_ct_vars(env, task_meta, None) {
+            out.push_str(&format!("\n        {}={:?}", name, value));
+        }
+
+        let env_files =
+            load_env_files(env, task_meta, |n| std::env::var(n).ok());
+        if !env_files.is_empty() {
+            let names = env_files.iter().map(|(n, _)| n).collect::<Vec<_>>();
+            out.push_str(&format!("\n        env files: {:?}", names));
+        }
+
+        let exporters = task_list[..idx]
+            .iter()
+            .filter(|t| env.tasks.contains_key(t.as_str()))
+            .filter(|t| export_visible(env, t, task_name))
+            .collect::<Vec<_>>();
+        if !exporters.is_empty() {
+            out.push_str(&format!("\n        exports from: {:?}", exporters));
+        }
+
+        let producers = task_list[..idx]
+            .iter()
+            .filter(|t| depends_on(env, task_name, t, &mut Default::default()))
+            .collect::<Vec<_>>();
+        if !producers.is_empty() {
+            out.push_str(&format!("\n        outputs from: {:?}", producers));
+        }
+    }
+
+    ct_info!("{}", out);
+}
@@ -11,12 +11,26 @@ pub fn help() {
              cargo task - execute all configured default cargo tasks
  cargo task [task-list] - execute a specific list of cargo tasks
 
+# flags #
+
+              --offline - build tasks without accessing the network
+               --locked - require .cargo-task/Cargo.lock is up-to-date
+               --frozen - equivalent to --offline and --locked
+          --each-member - run the task list in each cargo workspace member
+
 # system tasks #
 
                 ct-init - generate a '{}' directory + .gitignore
                 ct-meta - print meta info about the cargo-task configuration
-               ct-clean - delete the cargo-task target directory, will be
-                          removed even if it matches your project target dir
+               ct-clean - delete the cargo-task target directory, if this
+                          is your project target dir, only cargo-task build
+                          artifacts are removed
+               ct-build - build all tasks up-front in a single cargo build
+                          (set CT_PREBUILD=1 to do this on every run)
+     ct-eject [task...] - convert '*.ct.rs' script tasks into crate tasks
+    ct-inline [task...] - convert simple crate tasks into '*.ct.rs' scripts
+      ct-plan [task...] - print the task order and each task's environment
+              ct-doctor - check the cargo-task setup and report problems
 "#,
         CARGO_TASK_DIR,
     );
@@ -0,0 +1,187 @@
+//! Checking (and installing) the tools tasks require via
+//! `@ct-requires-tools@`.
+
+use crate::{_cargo_task_util::ToolStatus, *};
+use std::{collections::BTreeMap, ffi::OsString};
+
+/// A required tool, e.g. `cargo-readme>=3`.
+#[derive(Debug, Clone)]
+pub struct ToolReq {
+    /// the tool binary / cargo subcommand name
+    pub name: String,
+
+    /// required version, as reported by `<tool> --version`
+    pub version_req: Option<_cargo_task_util::CTVersionReq>,
+}
+
+impl std::fmt::Display for ToolReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version_req {
+            Some(version_req) => write!(f, "{}{}", self.name, version_req),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Parse a `name` or `name<version req>` tool requirement,
+/// e.g. `cargo-readme>=3` or `cargo-readme>=3,<4` (no spaces).
+pub fn parse_tool_req(s: &str) -> Result<ToolReq, String> {
+    let idx = s
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(s.len());
+    let (name, req) = s.split_at(idx);
+    if name.is_empty() {
+        return Err(format!("invalid tool requirement '{}'", s));
+    }
+    let version_req = if req.is_empty() {
+        None
+    } else {
+        Some(
+            _cargo_task_util::CTVersionReq::parse(req)
+                .map_err(|e| format!("tool '{}': {}", name, e))?,
+        )
+    };
+    Ok(ToolReq {
+        name: name.to_string(),
+        version_req,
+    })
+}
+
+/// Look for a tool as a cargo subcommand, then on PATH.
+/// `vars` is the environment the tool will be run in.
+pub fn probe_tool(
+    env: &_cargo_task_util::CTEnv,
+    req: &ToolReq,
+    vars: &BTreeMap<OsString, OsString>,
+) -> ToolStatus {
+    // `cargo rustfmt` is not a thing, rustfmt's subcommand is `cargo fmt`
+    let sub = match req.name.strip_prefix("cargo-").unwrap_or(&req.name) {
+        "rustfmt" => "fmt",
+        sub => sub,
+    };
+    env.probe_tool(sub, Some(&req.name), req.version_req.as_ref(), Some(vars))
+}
+
+/// Install a tool, as a rustup component for `clippy` / `rustfmt`,
+/// otherwise with `cargo install`.
+pub fn install_tool(
+    env: &_cargo_task_util::CTEnv,
+    req: &ToolReq,
+    vars: &BTreeMap<OsString, OsString>,
+) -> Result<(), String> {
+    let mut tool = match req.name.as_str() {
+        "clippy" | "cargo-clippy" => _cargo_task_util::CTTool::new("clippy"),
+        "rustfmt" | "cargo-fmt" => _cargo_task_util::CTTool::new("fmt"),
+        name => _cargo_task_util::CTTool::new(
+            name.strip_prefix("cargo-").unwrap_or(name),
+        )
+        .krate(name),
+    };
+    if let Some(version_req) = &req.version_req {
+        tool = tool.min_version(version_req.to_string());
+    }
+    if env.managed_tools {
+        tool = tool.managed();
+    }
+
+    ct_info!("install tool: {}", req);
+    env.install_tool(&tool, Some(vars))
+        .map_err(|e| e.to_string())
+}
+
+/// Put the managed tool root on the PATH of `vars`, if it exists
+/// and `install-tools` is `managed`.
+pub fn add_tool_root(
+    env: &_cargo_task_util::CTEnv,
+    vars: &mut BTreeMap<OsString, OsString>,
+) {
+    let bin = env.tool_root().join("bin");
+    if !env.managed_tools || !bin.is_dir() {
+        return;
+    }
+    let mut paths = vars
+        .get(&OsString::from("PATH"))
+        .map(|p| std::env::split_paths(p).collect::<Vec<_>>())
+        .unwrap_or_default();
+    if !paths.contains(&bin) {
+        paths.insert(0, bin);
+        vars.insert(
+            "PATH".into(),
+            ct_check_fatal!(std::env::join_paths(paths)),
+        );
+    }
+}
+
+/// Make sure the `@ct-requires-tools@` of a task are available.
+/// Missing tools are installed if `install-tools` is configured,
+/// otherwise this is fatal, listing all the missing tools.
+/// Managed tools are put on the PATH of `vars`.
+pub fn check_task_tools(
+    env: &_cargo_task_util::CTEnv,
+    task_meta: &_cargo_task_util::CTTaskMeta,
+    vars: &mut BTreeMap<OsString, OsString>,
+) {
+    let mut missing = Vec::new();
+
+    if !task_meta.requires_tools.is_empty() {
+        add_tool_root(env, vars);
+    }
+
+    for req in task_meta.requires_tools.iter() {
+        let req = match parse_tool_req(req) {
+            Ok(req) => req,
+            Err(e) => ct_fatal!("task '{}' {}", task_meta.name, e),
+        };
+        let mut status = probe_tool(env, &req, vars);
+        if env.install_tools && !matches!(status, ToolStatus::Ok(_)) {
+            if let Err(e) = install_tool(env, &req, vars) {
+                ct_warn!("{}", e);
+            }
+            add_tool_root(env, vars);
+            status = probe_tool(env, &req, vars);
+        }
+        match status {
+            ToolStatus::Ok(_) => (),
+            ToolStatus::TooOld(version) => {
+                missing.push(format!("{} (found {})", req, version))
+            }
+            ToolStatus::Missing => missing.push(format!("{} (not found)", req)),
+        }
+    }
+
+    if !missing.is_empty() {
+        ct_fatal!(
+            "task '{}' requires missing tools:\n  - {}\n{}",
+            task_meta.name,
+            missing.join("\n  - "),
+            if env.install_tools {
+                "they could not be installed automatically, \
+                please install them manually"
+            } else {
+                "install them, or set `@ct-install-tools@ true @@` \
+                in .cargo-task/config.atat to install them automatically"
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_reqs() {
+        let req = parse_tool_req("cargo-readme>=3,<4").unwrap();
+        assert_eq!("cargo-readme", req.name);
+        assert_eq!("cargo-readme>=3,<4", req.to_string());
+
+        let req = parse_tool_req("clippy").unwrap();
+        assert_eq!("clippy", req.name);
+        assert!(req.version_req.is_none());
+
+        assert!(parse_tool_req("git~2.30").is_ok());
+        assert!(parse_tool_req("git!=2").is_err());
+        assert!(parse_tool_req("git=>2").is_err());
+        assert!(parse_tool_req(">=2").is_err());
+    }
+}
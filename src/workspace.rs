@@ -0,0 +1,315 @@
+//! Minimal cargo workspace discovery from the project root Cargo.toml.
+
+use std::path::{Path, PathBuf};
+
+/// A crate that is part of the project's cargo workspace.
+#[derive(Debug, Clone)]
+pub struct WorkspaceMember {
+    /// the crate (package) name
+    pub name: String,
+
+    /// the directory containing the crate's Cargo.toml
+    pub dir: PathBuf,
+}
+
+/// Enumerate the crates of the cargo workspace rooted at `root`.
+/// Includes the root package (if any) and all `[workspace] members`
+/// (with glob expansion) that are not listed in `[workspace] exclude`.
+pub fn workspace_members<P: AsRef<Path>>(root: P) -> Vec<WorkspaceMember> {
+    let root = root.as_ref();
+    let mut out = Vec::new();
+
+    let content = match read_cargo_toml(root) {
+        Some(content) => content,
+        None => return out,
+    };
+
+    if let Some(name) = package_name(&content) {
+        out.push(WorkspaceMember {
+            name,
+            dir: root.to_owned(),
+        });
+    }
+
+    let members = toml_str_array(&content, "[workspace]", "members");
+    let exclude = toml_str_array(&content, "[workspace]", "exclude")
+        .into_iter()
+        .map(|e| root.join(e))
+        .collect::<Vec<_>>();
+
+    for pattern in members {
+        for dir in expand_glob(root, &pattern) {
+            if exclude.contains(&dir) || dir == root {
+                continue;
+            }
+            let content = match read_cargo_toml(&dir) {
+                Some(content) => content,
+                None => continue,
+            };
+            if let Some(name) = package_name(&content) {
+                out.push(WorkspaceMember { name, dir });
+            }
+        }
+    }
+
+    out.sort_by(|a, b| a.name.cmp(&b.name));
+    out
+}
+
+/// Find the cargo workspace root, searching upward from `dir`
+/// for a Cargo.toml with a `[workspace]` section.
+/// Falls back to `dir` if there is none.
+pub fn workspace_root<P: AsRef<Path>>(dir: P) -> PathBuf {
+    let dir = dir.as_ref();
+    let mut cur = dir.to_owned();
+    loop {
+        if let Some(content) = read_cargo_toml(&cur) {
+            if content.lines().any(|l| l.trim() == "[workspace]") {
+                return cur;
+            }
+        }
+        if !cur.pop() {
+            return dir.to_owned();
+        }
+    }
+}
+
+/// read the Cargo.toml file in a directory
+fn read_cargo_toml(dir: &Path) -> Option<String> {
+    std::fs::read_to_string(dir.join("Cargo.toml")).ok()
+}
+
+/// get the `[package] name` from Cargo.toml content
+fn package_name(content: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if in_package {
+            if let Some(value) = line.strip_prefix("name") {
+                if let Some(value) = value.trim_start().strip_prefix('=') {
+                    return Some(value.trim().trim_matches('"').to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// get a (possibly multi-line) array of strings from Cargo.toml content
+pub fn toml_str_array(content: &str, section: &str, key: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut in_section = false;
+    let mut in_array = false;
+
+    for line in content.lines() {
+        let mut line = line.trim();
+        if !in_array {
+            if line.starts_with('[') {
+                in_section = line == section;
+                continue;
+            }
+            if !in_section {
+                continue;
+            }
+            let value = match line.strip_prefix(key) {
+                Some(value) => value.trim_start(),
+                None => continue,
+            };
+            let value = match value.strip_prefix('=') {
+                Some(value) => value.trim_start(),
+                None => continue,
+            };
+            line = match value.strip_prefix('[') {
+                Some(value) => value,
+                None => continue,
+            };
+            in_array = true;
+        }
+
+        // strip trailing comments
+        let line = match line.find('#') {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+
+        let (items, done) = match line.find(']') {
+            Some(idx) => (&line[..idx], true),
+            None => (line, false),
+        };
+
+        for item in items.split(',') {
+            let item = item.trim().trim_matches('"');
+            if !item.is_empty() {
+                out.push(item.to_string());
+            }
+        }
+
+        if done {
+            break;
+        }
+    }
+
+    out
+}
+
+/// expand a cargo workspace member path glob into directories
+fn expand_glob(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_owned()];
+
+    for segment in pattern.split(['/', '\\']) {
+        if segment.is_empty() || segment == "." {
+            continue;
+        }
+        let mut next = Vec::new();
+        for dir in dirs {
+            if !segment.contains(['*', '?']) {
+                let dir = dir.join(segment);
+                if dir.is_dir() {
+                    next.push(dir);
+                }
+                continue;
+            }
+            let items = match std::fs::read_dir(&dir) {
+                Ok(items) => items,
+                Err(_) => continue,
+            };
+            for item in items.flatten() {
+                let name = item.file_name().to_string_lossy().to_string();
+                if item.path().is_dir() && glob_match(segment, &name) {
+                    next.push(item.path());
+                }
+            }
+        }
+        dirs = next;
+    }
+
+    dirs.sort();
+    dirs
+}
+
+/// match a single path segment against a glob with `*` and `?` wildcards
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    // (pattern index, name index) to resume from on mismatch
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((bp, bn)) = backtrack {
+            p = bp + 1;
+            n = bn + 1;
+            backtrack = Some((bp, bn + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Determine the cargo target directory for the project at `work_dir`.
+/// Checks `CARGO_TARGET_DIR`, then `build.target-dir` in any
+/// `.cargo/config.toml` from `work_dir` upward, defaulting to `target`.
+/// `CARGO_TARGET_DIR` and the default are relative to the workspace root.
+pub fn project_target_dir<P: AsRef<Path>>(work_dir: P) -> PathBuf {
+    let work_dir = work_dir.as_ref();
+    let root = workspace_root(work_dir);
+
+    if let Some(target_dir) = std::env::var_os("CARGO_TARGET_DIR") {
+        return root.join(target_dir);
+    }
+
+    let mut dir = work_dir.to_owned();
+    loop {
+        for config in ["config.toml", "config"].iter() {
+            let path = dir.join(".cargo").join(config);
+            let content = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+            if let Some(target_dir) = config_target_dir(&content) {
+                // relative to the directory containing the .cargo dir
+                return dir.join(target_dir);
+            }
+        }
+        if !dir.pop() {
+            break;
+        }
+    }
+
+    root.join("target")
+}
+
+/// get the `build.target-dir` from cargo config content
+fn config_target_dir(content: &str) -> Option<String> {
+    let mut in_build = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_build = line == "[build]";
+            continue;
+        }
+        let value = if in_build {
+            line.strip_prefix("target-dir")
+        } else {
+            line.strip_prefix("build.target-dir")
+        };
+        if let Some(value) =
+            value.and_then(|v| v.trim_start().strip_prefix('='))
+        {
+            return Some(value.trim().trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_segments() {
+        assert!(glob_match("*", "crate-a"));
+        assert!(glob_match("crate-*", "crate-a"));
+        assert!(glob_match("crate-?", "crate-a"));
+        assert!(glob_match("*-a*", "crate-a-b"));
+        assert!(glob_match("c*e*a", "crate-a"));
+        assert!(!glob_match("crate-?", "crate-ab"));
+        assert!(!glob_match("crate-*", "other"));
+        assert!(!glob_match("", "crate-a"));
+    }
+
+    #[test]
+    fn toml_str_array_values() {
+        let content = r#"
+[package]
+members = ["not", "this"]
+
+[workspace]
+exclude = ["x"]
+members = [
+    "crates/*", # comment
+    "tools/a",
+]
+"#;
+        assert_eq!(
+            vec!["crates/*", "tools/a"],
+            toml_str_array(content, "[workspace]", "members"),
+        );
+        assert_eq!(
+            vec!["x"],
+            toml_str_array(content, "[workspace]", "exclude"),
+        );
+        assert!(toml_str_array(content, "[workspace]", "missing").is_empty());
+    }
+}